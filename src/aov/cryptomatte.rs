@@ -0,0 +1,168 @@
+use crate::color::Color;
+use crate::framebuffer::Framebuffer;
+use crate::scene::Scene;
+use super::{Layer,Surface};
+
+// ID mattes in the Cryptomatte layout: every rank pair stores two IDs with
+// their coverage in one RGBA layer, IDs being name hashes read as floats
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub enum Matte {
+    Object,
+    Material,
+}
+
+impl Matte {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "object" => Some(Matte::Object),
+            "material" => Some(Matte::Material),
+            _ => None,
+        }
+    }
+
+    pub fn layer(&self) -> &'static str {
+        match self {
+            Matte::Object => "CryptoObject",
+            Matte::Material => "CryptoMaterial",
+        }
+    }
+
+    pub fn names<'s>(&self, scene: &'s Scene) -> Vec<&'s str> {
+        match self {
+            Matte::Object => scene.objects.iter().map(String::as_str).collect(),
+            Matte::Material => scene.materials.iter().map(|material| material.name.as_str()).collect(),
+        }
+    }
+
+    // the ID of every name, indexed like the scene objects or materials
+    pub fn ids(&self, scene: &Scene) -> Vec<u32> {
+        self.names(scene).iter().map(|name| hash(name)).collect()
+    }
+
+    pub fn index(&self, surface: &Surface) -> usize {
+        match self {
+            Matte::Object => surface.object - 1,
+            Matte::Material => surface.material - 1,
+        }
+    }
+}
+
+// MurmurHash3 x86 32-bit with seed 0, as the Cryptomatte specification asks
+fn murmur3(data: &[u8]) -> u32 {
+    let (c1, c2) = (0xcc9e_2d51u32, 0x1b87_3593u32);
+    let mut hash = 0u32;
+    let chunks = data.chunks_exact(4);
+    let tail = chunks.remainder();
+
+    for chunk in chunks {
+        let mut k = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        k = k.wrapping_mul(c1).rotate_left(15).wrapping_mul(c2);
+        hash ^= k;
+        hash = hash.rotate_left(13).wrapping_mul(5).wrapping_add(0xe654_6b64);
+    }
+
+    if !tail.is_empty() {
+        let k = tail.iter().rev().fold(0u32, |k, byte| k << 8 | *byte as u32);
+        hash ^= k.wrapping_mul(c1).rotate_left(15).wrapping_mul(c2);
+    }
+
+    hash ^= data.len() as u32;
+    hash ^= hash >> 16;
+    hash = hash.wrapping_mul(0x85eb_ca6b);
+    hash ^= hash >> 13;
+    hash = hash.wrapping_mul(0xc2b2_ae35);
+    hash ^ (hash >> 16)
+}
+
+// name hash with its exponent nudged so the float is neither denormal, infinite
+// nor NaN
+pub fn hash(name: &str) -> u32 {
+    let hash = murmur3(name.as_bytes());
+    let exponent = hash >> 23 & 0xff;
+    if exponent == 0 || exponent == 0xff { hash ^ 1 << 23 } else { hash }
+}
+
+// how many samples of one pixel landed on each ID
+#[derive(Default)]
+pub struct Coverage {
+    pub entries: Vec<(u32, u32)>,
+}
+
+impl Coverage {
+    pub fn add(&mut self, id: u32) {
+        match self.entries.iter_mut().find(|entry| entry.0 == id) {
+            Some(entry) => entry.1 += 1,
+            None => self.entries.push((id, 1)),
+        }
+    }
+
+    // the strongest `ranks` IDs with the fraction of `samples` they cover,
+    // ties broken by ID to stay deterministic
+    pub fn ranked(&self, ranks: usize, samples: u32) -> Vec<(u32, f64)> {
+        let mut entries = self.entries.clone();
+        entries.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        entries
+            .iter()
+            .take(ranks)
+            .map(|(id, count)| (*id, *count as f64 / samples.max(1) as f64))
+            .collect()
+    }
+}
+
+// `ranks` IDs per pixel packed two to a layer; `pixels` holds the ranked
+// coverage of every pixel in scanline order
+pub fn layers(matte: Matte, pixels: &[Vec<(u32, f64)>], width: u32, height: u32, ranks: usize) -> Vec<Layer> {
+    (0..ranks.div_ceil(2))
+        .map(|pair| {
+            let mut framebuffer = Framebuffer::new(width, height);
+            for (index, ranked) in pixels.iter().enumerate() {
+                let rank = |rank: usize| {
+                    ranked.get(rank).map_or((0., 0.), |(id, weight)| (f32::from_bits(*id) as f64, *weight))
+                };
+                let (first, first_weight) = rank(pair * 2);
+                let (second, second_weight) = rank(pair * 2 + 1);
+                framebuffer.pixels[index] = Color::new(first, first_weight, second);
+                framebuffer.alpha[index] = second_weight;
+            }
+
+            Layer {
+                name: format!("{}{:02}", matte.layer(), pair),
+                channels: &["R", "G", "B", "A"],
+                framebuffer,
+            }
+        })
+        .collect()
+}
+
+fn escape(name: &str) -> String {
+    name.chars()
+        .flat_map(|c| match c {
+            '"' | '\\' => vec!['\\', c],
+            c if (c as u32) < 0x20 => format!("\\u{:04x}", c as u32).chars().collect(),
+            c => vec![c],
+        })
+        .collect()
+}
+
+// header attributes naming the layers, hash and every ID's source name
+pub fn metadata(matte: Matte, scene: &Scene) -> Vec<(String, String)> {
+    let layer = matte.layer();
+    let names = matte.names(scene);
+    let key = format!("cryptomatte/{}", &format!("{:08x}", murmur3(layer.as_bytes()))[..7]);
+
+    let mut unique = names;
+    unique.sort_unstable();
+    unique.dedup();
+    let manifest = unique
+        .iter()
+        .map(|name| format!("\"{}\":\"{:08x}\"", escape(name), hash(name)))
+        .collect::<Vec<_>>()
+        .join(",");
+
+    vec![
+        (format!("{}/name", key), layer.to_string()),
+        (format!("{}/hash", key), "MurmurHash3_32".to_string()),
+        (format!("{}/conversion", key), "uint32_to_float32".to_string()),
+        (format!("{}/manifest", key), format!("{{{}}}", manifest)),
+    ]
+}
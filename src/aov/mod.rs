@@ -0,0 +1,180 @@
+pub mod cryptomatte;
+
+use crate::color::{Color,BLACK};
+use crate::framebuffer::Framebuffer;
+use crate::geometry::{Point,Vector};
+use crate::integrator::{Context,shading_normal};
+
+// per-pixel data about the first surface camera rays hit, for compositing
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub enum Aov {
+    Depth,
+    Normal,
+    Albedo,
+    Position,
+    Triangle,
+    Object,
+    Material,
+    Uv,
+    Mask,
+}
+
+pub const ALL: [Aov; 9] = [
+    Aov::Depth,
+    Aov::Normal,
+    Aov::Albedo,
+    Aov::Position,
+    Aov::Triangle,
+    Aov::Object,
+    Aov::Material,
+    Aov::Uv,
+    Aov::Mask,
+];
+
+impl Aov {
+    pub fn from_name(name: &str) -> Option<Self> {
+        let name = name.to_lowercase();
+        ALL.iter().copied().find(|aov| aov.name() == name)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Aov::Depth => "depth",
+            Aov::Normal => "normal",
+            Aov::Albedo => "albedo",
+            Aov::Position => "position",
+            Aov::Triangle => "triangle",
+            Aov::Object => "object",
+            Aov::Material => "material",
+            Aov::Uv => "uv",
+            Aov::Mask => "mask",
+        }
+    }
+
+    // one value per pixel, kept in the red channel
+    pub fn is_scalar(&self) -> bool {
+        matches!(self, Aov::Depth | Aov::Triangle | Aov::Object | Aov::Material | Aov::Mask)
+    }
+
+    pub fn channels(&self) -> &'static [&'static str] {
+        if self.is_scalar() { &["Y"] } else { &["R", "G", "B"] }
+    }
+
+    // values within [0, 1], which display formats store without clamping
+    pub fn is_bounded(&self) -> bool {
+        matches!(self, Aov::Albedo | Aov::Uv | Aov::Mask)
+    }
+
+    // IDs cannot be blended, so they come from the sample at the pixel center
+    pub fn is_id(&self) -> bool {
+        matches!(self, Aov::Triangle | Aov::Object | Aov::Material)
+    }
+}
+
+// extra image data stored next to the render; `channels` name the color
+// components in order, an `A` channel reads the alpha
+#[derive(Clone)]
+pub struct Layer {
+    pub name: String,
+    pub channels: &'static [&'static str],
+    pub framebuffer: Framebuffer,
+}
+
+// the first surface along a camera ray; IDs count from 1, leaving 0 for misses
+pub struct Surface {
+    depth: f64,
+    normal: Vector<'static>,
+    albedo: Color,
+    position: Point,
+    triangle: usize,
+    object: usize,
+    material: usize,
+    uv: Option<(f64, f64)>,
+}
+
+impl Surface {
+    // `forward` is the unit camera axis, depth is measured along it
+    pub fn find(context: &Context, origin: &Point, direction: &Vector, forward: &Vector) -> Option<Self> {
+        let direction: Vector = direction.normalize();
+        let hit = context.intersect(origin, &direction)?;
+        let lookup = context.lookup(&hit, &direction, hit.distance);
+        let material = context.scene.material(hit.trigon).textured(&lookup);
+        let normal = shading_normal(&hit, &material, &lookup).unwrap_or_else(|| hit.normal.clone());
+
+        Some(Surface {
+            depth: hit.distance * direction.dot_product(forward),
+            normal,
+            albedo: material.diffuse,
+            position: hit.point.clone(),
+            triangle: hit.trigon.index + 1,
+            object: hit.trigon.object + 1,
+            material: hit.trigon.material + 1,
+            uv: lookup.uv,
+        })
+    }
+
+    pub fn value(&self, aov: Aov) -> Color {
+        match aov {
+            Aov::Depth => Color::gray(self.depth),
+            Aov::Normal => Color::new(self.normal.x, self.normal.y, self.normal.z),
+            Aov::Albedo => self.albedo,
+            Aov::Position => Color::new(self.position.x, self.position.y, self.position.z),
+            Aov::Triangle => Color::gray(self.triangle as f64),
+            Aov::Object => Color::gray(self.object as f64),
+            Aov::Material => Color::gray(self.material as f64),
+            Aov::Uv => self.uv.map_or(BLACK, |(u, v)| Color::new(u, v, 0.)),
+            Aov::Mask => Color::gray(1.),
+        }
+    }
+}
+
+// running totals for one pixel: blended AOVs average over the samples that
+// hit something, the mask over all of them
+pub struct Accumulator {
+    pub totals: Vec<Color>,
+    pub center: Vec<Color>,
+    pub hits: u32,
+    pub samples: u32,
+}
+
+impl Accumulator {
+    pub fn new(aovs: &[Aov]) -> Self {
+        Accumulator {
+            totals: vec![BLACK; aovs.len()],
+            center: vec![BLACK; aovs.len()],
+            hits: 0,
+            samples: 0,
+        }
+    }
+
+    pub fn add(&mut self, aovs: &[Aov], surface: Option<&Surface>) {
+        let first = self.samples == 0;
+        self.samples += 1;
+
+        let surface = match surface {
+            Some(surface) => surface,
+            None => return,
+        };
+        self.hits += 1;
+
+        for (index, aov) in aovs.iter().enumerate() {
+            let value = surface.value(*aov);
+            self.totals[index] += value;
+            if first {
+                self.center[index] = value;
+            }
+        }
+    }
+
+    pub fn values(&self, aovs: &[Aov]) -> Vec<Color> {
+        aovs.iter()
+            .enumerate()
+            .map(|(index, aov)| match aov {
+                Aov::Mask => Color::gray(self.hits as f64 / self.samples.max(1) as f64),
+                aov if aov.is_id() => self.center[index],
+                _ if self.hits == 0 => BLACK,
+                _ => self.totals[index] * (1. / self.hits as f64),
+            })
+            .collect()
+    }
+}
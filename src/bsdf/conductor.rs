@@ -0,0 +1,97 @@
+use crate::color::{Color,BLACK};
+use crate::geometry::Vector;
+use super::microfacet::Ggx;
+use super::{Bsdf,Direction,Sample,fresnel_conductor,reflect,same_hemisphere};
+
+// GGX microfacet metal
+pub struct Conductor {
+    pub eta: Color,
+    pub k: Color,
+    pub roughness: f64,
+}
+
+impl Conductor {
+    // a dielectric-style index that yields `reflectance` at normal incidence
+    pub fn from_reflectance(reflectance: Color, roughness: f64) -> Self {
+        let eta = reflectance.map(|r| {
+            let root = r.clamp(0., 0.9999).sqrt();
+            (1. + root) / (1. - root)
+        });
+        Conductor { eta, k: BLACK, roughness }
+    }
+
+    fn half_vector(wo: &Direction, wi: &Direction) -> Direction {
+        let h: Direction = (wo + wi).normalize();
+        if h.z < 0. { h.multiply(-1.) } else { h }
+    }
+}
+
+impl Bsdf for Conductor {
+    fn evaluate(&self, wo: &Direction, wi: &Direction) -> Color {
+        if !same_hemisphere(wo, wi) {
+            return BLACK;
+        }
+        let ggx = Ggx::new(self.roughness);
+        let h = Conductor::half_vector(wo, wi);
+        let fresnel = fresnel_conductor(wo.dot_product(&h).abs(), self.eta, self.k);
+        fresnel * (ggx.d(&h) * ggx.g(wo, wi) / (4. * wo.z.abs() * wi.z.abs()))
+    }
+
+    fn pdf(&self, wo: &Direction, wi: &Direction) -> f64 {
+        if !same_hemisphere(wo, wi) {
+            return 0.;
+        }
+        let h = Conductor::half_vector(wo, wi);
+        Ggx::new(self.roughness).pdf(&h) / (4. * wo.dot_product(&h).abs())
+    }
+
+    fn sample(&self, wo: &Direction, u: (f64, f64), _choice: f64) -> Option<Sample> {
+        let mut h = Ggx::new(self.roughness).sample(u);
+        if wo.z < 0. {
+            h = h.multiply(-1.);
+        }
+        let wi = reflect(wo, &h);
+
+        if !same_hemisphere(wo, &wi) {
+            return None;
+        }
+
+        Some(Sample {
+            value: self.evaluate(wo, &wi),
+            pdf: self.pdf(wo, &wi),
+            wi,
+            specular: false,
+        })
+    }
+}
+
+// perfectly smooth mirror
+pub struct Mirror {
+    pub reflectance: Color,
+}
+
+impl Bsdf for Mirror {
+    fn evaluate(&self, _wo: &Direction, _wi: &Direction) -> Color {
+        BLACK
+    }
+
+    fn pdf(&self, _wo: &Direction, _wi: &Direction) -> f64 {
+        0.
+    }
+
+    fn sample(&self, wo: &Direction, _u: (f64, f64), _choice: f64) -> Option<Sample> {
+        if wo.z == 0. {
+            return None;
+        }
+        Some(Sample {
+            wi: Vector::new(-wo.x, -wo.y, wo.z),
+            value: self.reflectance * (1. / wo.z.abs()),
+            pdf: 1.,
+            specular: true,
+        })
+    }
+
+    fn is_specular(&self) -> bool {
+        true
+    }
+}
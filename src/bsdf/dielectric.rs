@@ -0,0 +1,181 @@
+use crate::color::{Color,BLACK,WHITE};
+use crate::geometry::Vector;
+use crate::geometry::optics::fresnel_dielectric;
+use super::microfacet::Ggx;
+use super::{Bsdf,Direction,Sample,reflect,refract,same_hemisphere};
+
+// indices on the side of `wo` and on the opposite side
+fn indices(wo: &Direction, ior: f64) -> (f64, f64) {
+    if wo.z > 0. { (1., ior) } else { (ior, 1.) }
+}
+
+// perfectly smooth glass
+pub struct Glass {
+    pub ior: f64,
+    pub transmission: Color,
+}
+
+impl Bsdf for Glass {
+    fn evaluate(&self, _wo: &Direction, _wi: &Direction) -> Color {
+        BLACK
+    }
+
+    fn pdf(&self, _wo: &Direction, _wi: &Direction) -> f64 {
+        0.
+    }
+
+    fn sample(&self, wo: &Direction, _u: (f64, f64), choice: f64) -> Option<Sample> {
+        if wo.z == 0. {
+            return None;
+        }
+
+        let (n_i, n_t) = indices(wo, self.ior);
+        let fresnel = fresnel_dielectric(wo.z.abs(), n_i, n_t);
+
+        if choice < fresnel {
+            return Some(Sample {
+                wi: Vector::new(-wo.x, -wo.y, wo.z),
+                value: WHITE * (fresnel / wo.z.abs()),
+                pdf: fresnel,
+                specular: true,
+            });
+        }
+
+        let normal = Vector::new(0., 0., 1f64.copysign(wo.z));
+        let wi = refract(wo, &normal, n_i / n_t)?;
+
+        Some(Sample {
+            value: self.transmission * ((1. - fresnel) / wi.z.abs()),
+            pdf: 1. - fresnel,
+            wi,
+            specular: true,
+        })
+    }
+
+    fn is_specular(&self) -> bool {
+        true
+    }
+}
+
+// GGX microfacet glass (Walter et al. 2007)
+pub struct RoughDielectric {
+    pub ior: f64,
+    pub roughness: f64,
+    pub transmission: Color,
+}
+
+struct Microfacet {
+    h: Direction,
+    reflection: bool,
+    eta: f64,
+    fresnel: f64,
+}
+
+impl RoughDielectric {
+    fn microfacet(&self, wo: &Direction, wi: &Direction) -> Option<Microfacet> {
+        if wo.z == 0. || wi.z == 0. {
+            return None;
+        }
+
+        let (n_i, n_t) = indices(wo, self.ior);
+        let reflection = same_hemisphere(wo, wi);
+        let eta = if reflection { 1. } else { n_t / n_i };
+
+        let h: Direction = (wo + &wi.multiply(eta)).normalize();
+        let h = if h.z < 0. { h.multiply(-1.) } else { h };
+
+        // microfacets seen from behind contribute nothing
+        if wo.dot_product(&h) * wo.z <= 0. || wi.dot_product(&h) * wi.z <= 0. {
+            return None;
+        }
+
+        let fresnel = fresnel_dielectric(wo.dot_product(&h).abs(), n_i, n_t);
+        Some(Microfacet { h, reflection, eta, fresnel })
+    }
+}
+
+impl Bsdf for RoughDielectric {
+    fn evaluate(&self, wo: &Direction, wi: &Direction) -> Color {
+        let facet = match self.microfacet(wo, wi) {
+            Some(facet) => facet,
+            None => return BLACK,
+        };
+
+        let ggx = Ggx::new(self.roughness);
+        let dg = ggx.d(&facet.h) * ggx.g(wo, wi);
+        let cos = (wo.z * wi.z).abs();
+
+        if facet.reflection {
+            return WHITE * (dg * facet.fresnel / (4. * cos));
+        }
+
+        let wo_h = wo.dot_product(&facet.h);
+        let wi_h = wi.dot_product(&facet.h);
+        let denominator = wo_h + facet.eta * wi_h;
+        let value = dg * facet.eta * facet.eta * (wi_h * wo_h).abs()
+            / (cos * denominator * denominator);
+
+        self.transmission * ((1. - facet.fresnel) * value)
+    }
+
+    fn pdf(&self, wo: &Direction, wi: &Direction) -> f64 {
+        let facet = match self.microfacet(wo, wi) {
+            Some(facet) => facet,
+            None => return 0.,
+        };
+
+        let pdf_h = Ggx::new(self.roughness).pdf(&facet.h);
+
+        if facet.reflection {
+            return pdf_h / (4. * wo.dot_product(&facet.h).abs()) * facet.fresnel;
+        }
+
+        let wi_h = wi.dot_product(&facet.h);
+        let denominator = wo.dot_product(&facet.h) + facet.eta * wi_h;
+        let jacobian = (facet.eta * facet.eta * wi_h).abs() / (denominator * denominator);
+        pdf_h * jacobian * (1. - facet.fresnel)
+    }
+
+    fn sample(&self, wo: &Direction, u: (f64, f64), choice: f64) -> Option<Sample> {
+        if wo.z == 0. {
+            return None;
+        }
+
+        let mut h = Ggx::new(self.roughness).sample(u);
+        if wo.z < 0. {
+            h = h.multiply(-1.);
+        }
+        if wo.dot_product(&h) <= 0. {
+            return None;
+        }
+
+        let (n_i, n_t) = indices(wo, self.ior);
+        let fresnel = fresnel_dielectric(wo.dot_product(&h).abs(), n_i, n_t);
+
+        let wi = if choice < fresnel {
+            let wi = reflect(wo, &h);
+            if !same_hemisphere(wo, &wi) {
+                return None;
+            }
+            wi
+        } else {
+            let wi = refract(wo, &h, n_i / n_t)?;
+            if same_hemisphere(wo, &wi) {
+                return None;
+            }
+            wi
+        };
+
+        let pdf = self.pdf(wo, &wi);
+        if pdf <= 0. {
+            return None;
+        }
+
+        Some(Sample {
+            value: self.evaluate(wo, &wi),
+            pdf,
+            wi,
+            specular: false,
+        })
+    }
+}
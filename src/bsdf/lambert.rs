@@ -0,0 +1,36 @@
+use std::f64::consts::PI;
+use crate::color::{Color,BLACK};
+use crate::geometry::Vector;
+use crate::sampling::cosine_hemisphere;
+use super::{Bsdf,Direction,Sample,same_hemisphere};
+
+pub struct Lambert {
+    pub albedo: Color,
+}
+
+impl Bsdf for Lambert {
+    fn evaluate(&self, wo: &Direction, wi: &Direction) -> Color {
+        if !same_hemisphere(wo, wi) {
+            return BLACK;
+        }
+        self.albedo * (1. / PI)
+    }
+
+    fn pdf(&self, wo: &Direction, wi: &Direction) -> f64 {
+        if !same_hemisphere(wo, wi) {
+            return 0.;
+        }
+        wi.z.abs() / PI
+    }
+
+    fn sample(&self, wo: &Direction, u: (f64, f64), _choice: f64) -> Option<Sample> {
+        let (x, y, z) = cosine_hemisphere(u.0, u.1);
+        let wi = Vector::new(x, y, z.copysign(wo.z));
+        Some(Sample {
+            value: self.evaluate(wo, &wi),
+            pdf: self.pdf(wo, &wi),
+            wi,
+            specular: false,
+        })
+    }
+}
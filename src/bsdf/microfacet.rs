@@ -0,0 +1,51 @@
+use std::f64::consts::PI;
+use crate::geometry::Vector;
+use super::Direction;
+
+const MIN_ALPHA: f64 = 1e-3;
+
+// isotropic GGX (Trowbridge-Reitz) distribution
+pub struct Ggx {
+    alpha: f64,
+}
+
+impl Ggx {
+    // perceptual roughness, squared into alpha
+    pub fn new(roughness: f64) -> Self {
+        Ggx { alpha: (roughness * roughness).max(MIN_ALPHA) }
+    }
+
+    pub fn d(&self, h: &Direction) -> f64 {
+        let cos2 = h.z * h.z;
+        let alpha2 = self.alpha * self.alpha;
+        let denominator = cos2 * (alpha2 - 1.) + 1.;
+        alpha2 / (PI * denominator * denominator)
+    }
+
+    fn lambda(&self, w: &Direction) -> f64 {
+        let cos2 = w.z * w.z;
+        if cos2 >= 1. {
+            return 0.;
+        }
+        let tan2 = (1. - cos2) / cos2;
+        ((1. + self.alpha * self.alpha * tan2).sqrt() - 1.) / 2.
+    }
+
+    pub fn g(&self, wo: &Direction, wi: &Direction) -> f64 {
+        1. / (1. + self.lambda(wo) + self.lambda(wi))
+    }
+
+    // half vector in the upper hemisphere, distributed as D(h) * cos(h)
+    pub fn sample(&self, u: (f64, f64)) -> Direction {
+        let (u1, u2) = u;
+        let tan2 = self.alpha * self.alpha * u1 / (1. - u1).max(1e-12);
+        let cos = 1. / (1. + tan2).sqrt();
+        let sin = (1. - cos * cos).max(0.).sqrt();
+        let phi = 2. * PI * u2;
+        Vector::new(sin * phi.cos(), sin * phi.sin(), cos)
+    }
+
+    pub fn pdf(&self, h: &Direction) -> f64 {
+        self.d(h) * h.z.abs()
+    }
+}
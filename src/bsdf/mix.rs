@@ -0,0 +1,73 @@
+use crate::color::Color;
+use super::{Bsdf,Direction,Sample};
+
+// linear blend: `weight` of `second`, the rest of `first`
+pub struct Mix {
+    pub first: Box<dyn Bsdf>,
+    pub second: Box<dyn Bsdf>,
+    pub weight: f64,
+}
+
+impl Bsdf for Mix {
+    fn evaluate(&self, wo: &Direction, wi: &Direction) -> Color {
+        self.first.evaluate(wo, wi) * (1. - self.weight)
+            + self.second.evaluate(wo, wi) * self.weight
+    }
+
+    fn pdf(&self, wo: &Direction, wi: &Direction) -> f64 {
+        self.first.pdf(wo, wi) * (1. - self.weight) + self.second.pdf(wo, wi) * self.weight
+    }
+
+    fn sample(&self, wo: &Direction, u: (f64, f64), choice: f64) -> Option<Sample> {
+        let (chosen, probability, choice) = if choice < self.weight {
+            (&self.second, self.weight, choice / self.weight)
+        } else {
+            (&self.first, 1. - self.weight, (choice - self.weight) / (1. - self.weight))
+        };
+
+        let sample = chosen.sample(wo, u, choice)?;
+
+        if sample.specular {
+            return Some(Sample {
+                value: sample.value * probability,
+                pdf: sample.pdf * probability,
+                ..sample
+            });
+        }
+
+        Some(Sample {
+            value: self.evaluate(wo, &sample.wi),
+            pdf: self.pdf(wo, &sample.wi),
+            ..sample
+        })
+    }
+
+    fn is_specular(&self) -> bool {
+        self.first.is_specular() && self.second.is_specular()
+    }
+}
+
+// multiplies every lobe of `bsdf` by a constant factor
+pub struct Scaled {
+    pub bsdf: Box<dyn Bsdf>,
+    pub factor: f64,
+}
+
+impl Bsdf for Scaled {
+    fn evaluate(&self, wo: &Direction, wi: &Direction) -> Color {
+        self.bsdf.evaluate(wo, wi) * self.factor
+    }
+
+    fn pdf(&self, wo: &Direction, wi: &Direction) -> f64 {
+        self.bsdf.pdf(wo, wi)
+    }
+
+    fn sample(&self, wo: &Direction, u: (f64, f64), choice: f64) -> Option<Sample> {
+        let sample = self.bsdf.sample(wo, u, choice)?;
+        Some(Sample { value: sample.value * self.factor, ..sample })
+    }
+
+    fn is_specular(&self) -> bool {
+        self.bsdf.is_specular()
+    }
+}
@@ -0,0 +1,290 @@
+mod microfacet;
+mod lambert;
+mod phong;
+mod conductor;
+mod dielectric;
+mod plastic;
+mod mix;
+mod principled;
+
+use crate::color::Color;
+use crate::geometry::Vector;
+
+pub use lambert::Lambert;
+pub use phong::BlinnPhong;
+pub use conductor::{Conductor,Mirror};
+pub use dielectric::{Glass,RoughDielectric};
+pub use plastic::Plastic;
+pub use mix::{Mix,Scaled};
+pub use principled::Principled;
+
+// Directions are in the local shading frame: the surface normal is +z and
+// both `wo` and `wi` point away from the surface.
+pub type Direction = Vector<'static>;
+
+pub struct Sample {
+    pub wi: Direction,
+    pub value: Color,
+    pub pdf: f64,
+    pub specular: bool,
+}
+
+pub trait Bsdf: Send + Sync {
+    fn evaluate(&self, wo: &Direction, wi: &Direction) -> Color;
+
+    fn pdf(&self, wo: &Direction, wi: &Direction) -> f64;
+
+    // `u` drives direction sampling, `choice` picks between lobes
+    fn sample(&self, wo: &Direction, u: (f64, f64), choice: f64) -> Option<Sample>;
+
+    // true when every lobe is a delta distribution, so light sampling is useless
+    fn is_specular(&self) -> bool {
+        false
+    }
+}
+
+pub fn same_hemisphere(a: &Direction, b: &Direction) -> bool {
+    a.z * b.z > 0.
+}
+
+pub fn reflect(wo: &Direction, normal: &Direction) -> Direction {
+    let scale = 2. * wo.dot_product(normal);
+    Vector::new(
+        -wo.x + normal.x * scale,
+        -wo.y + normal.y * scale,
+        -wo.z + normal.z * scale,
+    )
+}
+
+// `normal` lies on the side of `wo`, `eta` is n_incident / n_transmitted
+pub fn refract(wo: &Direction, normal: &Direction, eta: f64) -> Option<Direction> {
+    let cos_i = wo.dot_product(normal);
+    let sin2_t = eta * eta * (1. - cos_i * cos_i).max(0.);
+
+    if sin2_t >= 1. {
+        return None;
+    }
+
+    let cos_t = (1. - sin2_t).sqrt();
+    let scale = eta * cos_i - cos_t;
+
+    Some(Vector::new(
+        -wo.x * eta + normal.x * scale,
+        -wo.y * eta + normal.y * scale,
+        -wo.z * eta + normal.z * scale,
+    ))
+}
+
+// exact Fresnel reflectance for a complex index of refraction eta + ik
+pub fn fresnel_conductor(cos_i: f64, eta: Color, k: Color) -> Color {
+    let channel = |eta: f64, k: f64| {
+        let cos2 = cos_i * cos_i;
+        let sin2 = 1. - cos2;
+        let t0 = eta * eta - k * k - sin2;
+        let a2b2 = (t0 * t0 + 4. * eta * eta * k * k).sqrt();
+        let t1 = a2b2 + cos2;
+        let a = (0.5 * (a2b2 + t0)).max(0.).sqrt();
+        let t2 = 2. * cos_i * a;
+        let rs = (t1 - t2) / (t1 + t2);
+        let t3 = cos2 * a2b2 + sin2 * sin2;
+        let t4 = t2 * sin2;
+        let rp = rs * (t3 - t4) / (t3 + t4);
+        (rp + rs) / 2.
+    };
+
+    Color::new(
+        channel(eta.r, k.r),
+        channel(eta.g, k.g),
+        channel(eta.b, k.b),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+    use super::*;
+    use crate::color::{Color,WHITE};
+    use crate::sampling::{Rng,uniform_sphere};
+
+    fn direction(theta: f64) -> Direction {
+        let theta = theta.to_radians();
+        Vector::new(theta.sin(), 0., theta.cos())
+    }
+
+    // directional albedo estimated by importance sampling the BSDF
+    fn albedo(bsdf: &dyn Bsdf, wo: &Direction) -> Color {
+        let mut rng = Rng::new(7);
+        let count = 200_000;
+        let mut total = Color::gray(0.);
+
+        for _ in 0..count {
+            let u = (rng.next_f64(), rng.next_f64());
+            if let Some(sample) = bsdf.sample(wo, u, rng.next_f64()) {
+                if sample.pdf > 0. {
+                    total += sample.value * (sample.wi.z.abs() / sample.pdf);
+                }
+            }
+        }
+
+        total * (1. / count as f64)
+    }
+
+    // same integral estimated with uniform sphere sampling and `evaluate`
+    fn uniform_albedo(bsdf: &dyn Bsdf, wo: &Direction) -> Color {
+        let mut rng = Rng::new(11);
+        let count = 400_000;
+        let mut total = Color::gray(0.);
+
+        for _ in 0..count {
+            let (x, y, z) = uniform_sphere(rng.next_f64(), rng.next_f64());
+            let wi = Vector::new(x, y, z);
+            total += bsdf.evaluate(wo, &wi) * (z.abs() * 4. * PI);
+        }
+
+        total * (1. / count as f64)
+    }
+
+    fn assert_conserves(bsdf: &dyn Bsdf) {
+        for theta in [0., 30., 60., 80.] {
+            let albedo = albedo(bsdf, &direction(theta));
+            for value in [albedo.r, albedo.g, albedo.b] {
+                assert!(value <= 1.01, "albedo {} at {} degrees", value, theta);
+            }
+        }
+    }
+
+    fn assert_consistent(bsdf: &dyn Bsdf) {
+        for theta in [10., 45., 70.] {
+            let wo = direction(theta);
+            let sampled = albedo(bsdf, &wo).r;
+            let uniform = uniform_albedo(bsdf, &wo).r;
+            assert!(
+                (sampled - uniform).abs() < 0.03,
+                "sampled {} vs uniform {} at {} degrees",
+                sampled,
+                uniform,
+                theta
+            );
+        }
+    }
+
+    #[test]
+    fn lambert_reflects_albedo() {
+        let bsdf = Lambert { albedo: Color::gray(0.5) };
+        let albedo = albedo(&bsdf, &direction(40.));
+        assert!((albedo.r - 0.5).abs() < 1e-3);
+        assert_consistent(&bsdf);
+    }
+
+    #[test]
+    fn blinn_phong_conserves_energy() {
+        for exponent in [1., 10., 100., 1000.] {
+            let bsdf = BlinnPhong { specular: WHITE, exponent };
+            assert_conserves(&bsdf);
+        }
+        assert_consistent(&BlinnPhong { specular: WHITE, exponent: 20. });
+    }
+
+    #[test]
+    fn conductor_conserves_energy() {
+        for roughness in [0.05, 0.3, 0.8] {
+            let bsdf = Conductor::from_reflectance(WHITE, roughness);
+            assert_conserves(&bsdf);
+        }
+        assert_consistent(&Conductor::from_reflectance(Color::gray(0.9), 0.5));
+    }
+
+    #[test]
+    fn mirror_reflects_everything() {
+        let bsdf = Mirror { reflectance: WHITE };
+        let albedo = albedo(&bsdf, &direction(30.));
+        assert!((albedo.r - 1.).abs() < 1e-9);
+    }
+
+    #[test]
+    fn glass_splits_energy_between_reflection_and_transmission() {
+        let bsdf = Glass { ior: 1.5, transmission: WHITE };
+        for theta in [0., 45., 85.] {
+            let albedo = albedo(&bsdf, &direction(theta));
+            assert!((albedo.r - 1.).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn rough_dielectric_conserves_energy() {
+        for roughness in [0.1, 0.4, 0.8] {
+            let bsdf = RoughDielectric { ior: 1.5, roughness, transmission: WHITE };
+            assert_conserves(&bsdf);
+            let inside = Vector::new(0.3, 0., -0.95).normalize();
+            let albedo = albedo(&bsdf, &inside);
+            assert!(albedo.r <= 1.01, "albedo {} from inside", albedo.r);
+        }
+        assert_consistent(&RoughDielectric { ior: 1.5, roughness: 0.7, transmission: WHITE });
+    }
+
+    #[test]
+    fn principled_conserves_energy() {
+        let base = Principled::default();
+        let materials = [
+            Principled { metallic: 1., base_color: WHITE, roughness: 0.3, ..base.clone() },
+            Principled { roughness: 0.8, ..base.clone() },
+            Principled { clearcoat: 1., sheen: 0.5, ..base.clone() },
+            Principled { transmission: 1., base_color: WHITE, roughness: 0.2, ..base.clone() },
+        ];
+
+        for principled in materials {
+            assert_conserves(principled.bsdf().as_ref());
+        }
+        assert_consistent(Principled { metallic: 0.5, ..base }.bsdf().as_ref());
+    }
+
+    #[test]
+    fn layered_material_lobes_conserve_energy() {
+        use crate::material::Material;
+
+        let mut glossy = Material::new("glossy");
+        glossy.diffuse = WHITE;
+        glossy.specular = Color::gray(0.6);
+        glossy.shininess = 50.;
+        let mut mirror = Material::new("mirror");
+        mirror.diffuse = WHITE;
+        mirror.reflection = Color::gray(0.5);
+
+        for material in [glossy, mirror] {
+            assert_conserves(material.bsdf().as_ref());
+        }
+    }
+
+    #[test]
+    fn rough_coat_keeps_reflection_color() {
+        use crate::material::Material;
+
+        let coated = |reflection| {
+            let mut material = Material::new("coated");
+            material.diffuse = Color::gray(0.2);
+            material.reflection = reflection;
+            material.roughness = Some(0.2);
+            albedo(material.bsdf().as_ref(), &direction(60.))
+        };
+        let white = coated(WHITE);
+        let dim = coated(Color::gray(0.1));
+        let red = coated(Color::new(1., 0.1, 0.1));
+
+        assert!(white.r > dim.r + 0.01, "{} vs {}", white.r, dim.r);
+        assert!(red.r > red.g + 0.01, "{} vs {}", red.r, red.g);
+    }
+
+    #[test]
+    fn plastic_conserves_energy() {
+        for roughness in [0.05, 0.3, 0.8] {
+            let bsdf = Plastic { diffuse: WHITE, coating: WHITE, ior: 1.5, roughness };
+            assert_conserves(&bsdf);
+        }
+        assert_consistent(&Plastic {
+            diffuse: Color::gray(0.8),
+            coating: Color::gray(0.6),
+            ior: 1.5,
+            roughness: 0.4,
+        });
+    }
+}
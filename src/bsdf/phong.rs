@@ -0,0 +1,57 @@
+use std::f64::consts::PI;
+use crate::color::{Color,BLACK};
+use crate::geometry::Vector;
+use super::{Bsdf,Direction,Sample,reflect,same_hemisphere};
+
+// Blinn-Phong specular lobe, normalized to reflect everything at normal incidence
+pub struct BlinnPhong {
+    pub specular: Color,
+    pub exponent: f64,
+}
+
+impl BlinnPhong {
+    fn half_vector(wo: &Direction, wi: &Direction) -> Direction {
+        let h: Direction = (wo + wi).normalize();
+        if h.z < 0. { h.multiply(-1.) } else { h }
+    }
+}
+
+impl Bsdf for BlinnPhong {
+    fn evaluate(&self, wo: &Direction, wi: &Direction) -> Color {
+        if !same_hemisphere(wo, wi) {
+            return BLACK;
+        }
+        let h = BlinnPhong::half_vector(wo, wi);
+        let n = self.exponent;
+        let normalization = (n + 2.) * (n + 4.) / (8. * PI * (2f64.powf(-n / 2.) + n));
+        self.specular * (normalization * h.z.powf(self.exponent))
+    }
+
+    fn pdf(&self, wo: &Direction, wi: &Direction) -> f64 {
+        if !same_hemisphere(wo, wi) {
+            return 0.;
+        }
+        let h = BlinnPhong::half_vector(wo, wi);
+        let pdf_h = (self.exponent + 1.) / (2. * PI) * h.z.powf(self.exponent);
+        pdf_h / (4. * wo.dot_product(&h).abs())
+    }
+
+    fn sample(&self, wo: &Direction, u: (f64, f64), _choice: f64) -> Option<Sample> {
+        let cos = u.0.powf(1. / (self.exponent + 1.));
+        let sin = (1. - cos * cos).max(0.).sqrt();
+        let phi = 2. * PI * u.1;
+        let h = Vector::new(sin * phi.cos(), sin * phi.sin(), cos.copysign(wo.z));
+        let wi = reflect(wo, &h);
+
+        if !same_hemisphere(wo, &wi) {
+            return None;
+        }
+
+        Some(Sample {
+            value: self.evaluate(wo, &wi),
+            pdf: self.pdf(wo, &wi),
+            wi,
+            specular: false,
+        })
+    }
+}
@@ -0,0 +1,95 @@
+use std::f64::consts::PI;
+use crate::color::{Color,BLACK};
+use crate::geometry::Vector;
+use crate::geometry::optics::fresnel_dielectric;
+use crate::sampling::cosine_hemisphere;
+use super::microfacet::Ggx;
+use super::{Bsdf,Direction,Sample,reflect,same_hemisphere};
+
+const MIN_LOBE_PROBABILITY: f64 = 0.1;
+
+// diffuse base under a rough dielectric coating, its reflection tinted by
+// `coating`
+pub struct Plastic {
+    pub diffuse: Color,
+    pub coating: Color,
+    pub ior: f64,
+    pub roughness: f64,
+}
+
+impl Plastic {
+    fn fresnel(&self, cos: f64) -> f64 {
+        fresnel_dielectric(cos.abs(), 1., self.ior)
+    }
+
+    fn half_vector(wo: &Direction, wi: &Direction) -> Direction {
+        let h: Direction = (wo + wi).normalize();
+        if h.z < 0. { h.multiply(-1.) } else { h }
+    }
+
+    fn specular_probability(&self, wo: &Direction) -> f64 {
+        let coating = self.coating.r.max(self.coating.g).max(self.coating.b);
+        let specular = self.fresnel(wo.z) * coating;
+        let diffuse = self.diffuse.r.max(self.diffuse.g).max(self.diffuse.b)
+            * (1. - self.fresnel(wo.z));
+        let total = specular + diffuse;
+        if total <= 0. {
+            return 1.;
+        }
+        (specular / total).clamp(MIN_LOBE_PROBABILITY, 1. - MIN_LOBE_PROBABILITY)
+    }
+}
+
+impl Bsdf for Plastic {
+    fn evaluate(&self, wo: &Direction, wi: &Direction) -> Color {
+        if !same_hemisphere(wo, wi) {
+            return BLACK;
+        }
+
+        let ggx = Ggx::new(self.roughness);
+        let h = Plastic::half_vector(wo, wi);
+        let cos = (wo.z * wi.z).abs();
+        let coating = ggx.d(&h) * ggx.g(wo, wi) * self.fresnel(wo.dot_product(&h))
+            / (4. * cos);
+        let base = (1. - self.fresnel(wo.z)) * (1. - self.fresnel(wi.z)) / PI;
+
+        self.diffuse * base + self.coating * coating
+    }
+
+    fn pdf(&self, wo: &Direction, wi: &Direction) -> f64 {
+        if !same_hemisphere(wo, wi) {
+            return 0.;
+        }
+
+        let h = Plastic::half_vector(wo, wi);
+        let specular = Ggx::new(self.roughness).pdf(&h) / (4. * wo.dot_product(&h).abs());
+        let diffuse = wi.z.abs() / PI;
+        let probability = self.specular_probability(wo);
+
+        probability * specular + (1. - probability) * diffuse
+    }
+
+    fn sample(&self, wo: &Direction, u: (f64, f64), choice: f64) -> Option<Sample> {
+        let wi = if choice < self.specular_probability(wo) {
+            let mut h = Ggx::new(self.roughness).sample(u);
+            if wo.z < 0. {
+                h = h.multiply(-1.);
+            }
+            reflect(wo, &h)
+        } else {
+            let (x, y, z) = cosine_hemisphere(u.0, u.1);
+            Vector::new(x, y, z.copysign(wo.z))
+        };
+
+        if !same_hemisphere(wo, &wi) {
+            return None;
+        }
+
+        Some(Sample {
+            value: self.evaluate(wo, &wi),
+            pdf: self.pdf(wo, &wi),
+            wi,
+            specular: false,
+        })
+    }
+}
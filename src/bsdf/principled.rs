@@ -0,0 +1,286 @@
+use std::f64::consts::PI;
+use crate::color::{Color,BLACK,WHITE};
+use crate::geometry::Vector;
+use crate::sampling::cosine_hemisphere;
+use super::microfacet::Ggx;
+use super::{Bsdf,Direction,Glass,RoughDielectric,Sample,Scaled,reflect,same_hemisphere};
+
+const CLEARCOAT_REFLECTANCE: f64 = 0.04;
+
+// artist-facing parameters of the Disney principled BSDF, in the same
+// ranges as Blender and glTF metallic-roughness materials
+#[derive(Debug,Clone,PartialEq)]
+pub struct Principled {
+    pub base_color: Color,
+    pub metallic: f64,
+    pub roughness: f64,
+    pub specular: f64,
+    pub specular_tint: f64,
+    pub sheen: f64,
+    pub sheen_tint: f64,
+    pub clearcoat: f64,
+    pub clearcoat_roughness: f64,
+    pub transmission: f64,
+    pub ior: f64,
+}
+
+impl Default for Principled {
+    fn default() -> Self {
+        Principled {
+            base_color: Color::gray(0.8),
+            metallic: 0.,
+            roughness: 0.5,
+            specular: 0.5,
+            specular_tint: 0.,
+            sheen: 0.,
+            sheen_tint: 0.5,
+            clearcoat: 0.,
+            clearcoat_roughness: 0.03,
+            transmission: 0.,
+            ior: 1.45,
+        }
+    }
+}
+
+impl Principled {
+    // base color with its luminance normalized away
+    fn tint(&self) -> Color {
+        let luminance = self.base_color.luminance();
+        if luminance <= 0. {
+            return WHITE;
+        }
+        self.base_color * (1. / luminance)
+    }
+
+    pub fn bsdf(&self) -> Box<dyn Bsdf> {
+        let metallic = self.metallic.clamp(0., 1.);
+        let transmission = self.transmission.clamp(0., 1.) * (1. - metallic);
+        let diffuse = (1. - metallic) * (1. - self.transmission.clamp(0., 1.));
+        let tint = self.tint();
+
+        let mut lobes: Vec<(Box<dyn Bsdf>, f64)> = vec![];
+
+        if diffuse > 0. {
+            let sheen = lerp(WHITE, tint, self.sheen_tint) * self.sheen;
+            lobes.push((
+                Box::new(Diffuse {
+                    color: self.base_color * diffuse,
+                    sheen: sheen * diffuse,
+                    roughness: self.roughness,
+                    coating: 0.08 * self.specular + 0.25 * self.clearcoat * CLEARCOAT_REFLECTANCE,
+                }),
+                diffuse * max(self.base_color).max(self.sheen),
+            ));
+        }
+
+        let dielectric = lerp(WHITE, tint, self.specular_tint) * (0.08 * self.specular);
+        let reflectance = lerp(dielectric, self.base_color, metallic);
+        if transmission < 1. {
+            let specular = Specular { reflectance, roughness: self.roughness };
+            lobes.push((
+                Box::new(Scaled { bsdf: Box::new(specular), factor: 1. - transmission }),
+                (1. - transmission) * max(reflectance).max(CLEARCOAT_REFLECTANCE),
+            ));
+        }
+
+        if transmission > 0. {
+            let color = self.base_color.map(f64::sqrt) * transmission;
+            let glass: Box<dyn Bsdf> = if self.roughness > 0. {
+                Box::new(RoughDielectric { ior: self.ior, roughness: self.roughness, transmission: color })
+            } else {
+                Box::new(Glass { ior: self.ior, transmission: color })
+            };
+            lobes.push((glass, transmission));
+        }
+
+        if self.clearcoat > 0. {
+            let coat = Specular {
+                reflectance: Color::gray(CLEARCOAT_REFLECTANCE),
+                roughness: self.clearcoat_roughness,
+            };
+            let weight = 0.25 * self.clearcoat;
+            lobes.push((Box::new(Scaled { bsdf: Box::new(coat), factor: weight }), weight));
+        }
+
+        Box::new(Lobes::new(lobes))
+    }
+}
+
+fn lerp(a: Color, b: Color, t: f64) -> Color {
+    a * (1. - t) + b * t
+}
+
+fn max(color: Color) -> f64 {
+    color.r.max(color.g).max(color.b)
+}
+
+fn schlick(cos: f64) -> f64 {
+    (1. - cos.abs()).clamp(0., 1.).powi(5)
+}
+
+fn half_vector(wo: &Direction, wi: &Direction) -> Direction {
+    let h: Direction = (wo + wi).normalize();
+    if h.z < 0. { h.multiply(-1.) } else { h }
+}
+
+// Burley diffuse with retro-reflection and a sheen lobe at grazing angles,
+// dimmed by the light the specular and clearcoat layers reflect
+struct Diffuse {
+    color: Color,
+    sheen: Color,
+    roughness: f64,
+    coating: f64,
+}
+
+impl Bsdf for Diffuse {
+    fn evaluate(&self, wo: &Direction, wi: &Direction) -> Color {
+        if !same_hemisphere(wo, wi) {
+            return BLACK;
+        }
+
+        let cos_d = wi.dot_product(&half_vector(wo, wi));
+        let retro = 0.5 + 2. * self.roughness * cos_d * cos_d;
+        let fresnel_o = 1. + (retro - 1.) * schlick(wo.z);
+        let fresnel_i = 1. + (retro - 1.) * schlick(wi.z);
+
+        let coating = |cos: f64| 1. - (self.coating + (1. - self.coating) * schlick(cos));
+        let transmitted = coating(wo.z) * coating(wi.z);
+
+        (self.color * (fresnel_o * fresnel_i / PI) + self.sheen * schlick(cos_d)) * transmitted
+    }
+
+    fn pdf(&self, wo: &Direction, wi: &Direction) -> f64 {
+        if !same_hemisphere(wo, wi) {
+            return 0.;
+        }
+        wi.z.abs() / PI
+    }
+
+    fn sample(&self, wo: &Direction, u: (f64, f64), _choice: f64) -> Option<Sample> {
+        let (x, y, z) = cosine_hemisphere(u.0, u.1);
+        let wi = Vector::new(x, y, z.copysign(wo.z));
+        Some(Sample {
+            value: self.evaluate(wo, &wi),
+            pdf: self.pdf(wo, &wi),
+            wi,
+            specular: false,
+        })
+    }
+}
+
+// GGX reflection with Schlick's Fresnel approximation
+struct Specular {
+    reflectance: Color,
+    roughness: f64,
+}
+
+impl Specular {
+    fn fresnel(&self, cos: f64) -> Color {
+        lerp(self.reflectance, WHITE, schlick(cos))
+    }
+}
+
+impl Bsdf for Specular {
+    fn evaluate(&self, wo: &Direction, wi: &Direction) -> Color {
+        if !same_hemisphere(wo, wi) {
+            return BLACK;
+        }
+        let ggx = Ggx::new(self.roughness);
+        let h = half_vector(wo, wi);
+        let fresnel = self.fresnel(wo.dot_product(&h));
+        fresnel * (ggx.d(&h) * ggx.g(wo, wi) / (4. * wo.z.abs() * wi.z.abs()))
+    }
+
+    fn pdf(&self, wo: &Direction, wi: &Direction) -> f64 {
+        if !same_hemisphere(wo, wi) {
+            return 0.;
+        }
+        let h = half_vector(wo, wi);
+        Ggx::new(self.roughness).pdf(&h) / (4. * wo.dot_product(&h).abs())
+    }
+
+    fn sample(&self, wo: &Direction, u: (f64, f64), _choice: f64) -> Option<Sample> {
+        let mut h = Ggx::new(self.roughness).sample(u);
+        if wo.z < 0. {
+            h = h.multiply(-1.);
+        }
+        let wi = reflect(wo, &h);
+
+        if !same_hemisphere(wo, &wi) {
+            return None;
+        }
+
+        Some(Sample {
+            value: self.evaluate(wo, &wi),
+            pdf: self.pdf(wo, &wi),
+            wi,
+            specular: false,
+        })
+    }
+}
+
+// sum of lobes, each sampled with probability proportional to its weight
+struct Lobes {
+    lobes: Vec<(Box<dyn Bsdf>, f64)>,
+}
+
+impl Lobes {
+    fn new(lobes: Vec<(Box<dyn Bsdf>, f64)>) -> Self {
+        let total: f64 = lobes.iter().map(|(_, weight)| weight).sum();
+        let count = lobes.len() as f64;
+        let lobes = lobes
+            .into_iter()
+            .map(|(lobe, weight)| {
+                let probability = if total > 0. { weight / total } else { 1. / count };
+                (lobe, probability)
+            })
+            .collect();
+        Lobes { lobes }
+    }
+}
+
+impl Bsdf for Lobes {
+    fn evaluate(&self, wo: &Direction, wi: &Direction) -> Color {
+        self.lobes
+            .iter()
+            .fold(BLACK, |total, (lobe, _)| total + lobe.evaluate(wo, wi))
+    }
+
+    fn pdf(&self, wo: &Direction, wi: &Direction) -> f64 {
+        self.lobes
+            .iter()
+            .map(|(lobe, probability)| lobe.pdf(wo, wi) * probability)
+            .sum()
+    }
+
+    fn sample(&self, wo: &Direction, u: (f64, f64), choice: f64) -> Option<Sample> {
+        let mut choice = choice;
+        let (lobe, probability) = self
+            .lobes
+            .iter()
+            .find(|(_, probability)| {
+                if choice < *probability {
+                    return true;
+                }
+                choice -= probability;
+                false
+            })
+            .or_else(|| self.lobes.last())?;
+
+        let sample = lobe.sample(wo, u, (choice / probability).min(1.))?;
+
+        if sample.specular {
+            return Some(Sample { pdf: sample.pdf * probability, ..sample });
+        }
+
+        Some(Sample {
+            value: self.evaluate(wo, &sample.wi),
+            pdf: self.pdf(wo, &sample.wi),
+            ..sample
+        })
+    }
+
+    fn is_specular(&self) -> bool {
+        self.lobes.iter().all(|(lobe, _)| lobe.is_specular())
+    }
+}
@@ -1,39 +1,56 @@
-use crate::geometry::{Point};
-
-// TODO: use view vector
-pub fn build(
-    height: u32,
-    width: u32,
-    y: f64,
-    distance: f64,
-    fov: f32,
-) -> Vec<Vec<Point>> {
-    let mut canvas: Vec<Vec<Point>> = vec![];
-
-    let real_width = 2. * (fov.to_radians() / 2.).tan() as f64 * distance;
-    let delta = real_width / width as f64;
-    let real_height = delta * height as f64;
-
-    let x_start = -real_width / 2.;
-    let x_end = x_start + real_width as f64;
-    let z_start = real_height / 2.;
-    let z_end = z_start - real_height as f64;
-
-    let mut z = z_start;
-
-    while z >= z_end {
-        let mut row: Vec<Point> = vec![];
-        let mut x = x_start;
-
-        while x <= x_end {
-            let point = Point::new(x, y, z);
-            row.push(point);
-            x = x + delta;
+use crate::geometry::{Point,Vector};
+
+pub struct Canvas {
+    origin: Point,
+    right: Point,
+    down: Point,
+}
+
+impl Canvas {
+    pub fn new(
+        width: u32,
+        height: u32,
+        camera_pos: &Point,
+        target: &Point,
+        fov: f64,
+    ) -> Self {
+        let forward = Vector::from(target - camera_pos).normalize();
+
+        let mut right = forward.cross_product(&Vector::new(0., 0., 1.));
+        if right.length < f64::EPSILON {
+            right = forward.cross_product(&Vector::new(0., 1., 0.));
         }
+        let right = right.normalize();
+        let up = right.cross_product(&forward);
 
-        canvas.push(row);
-        z = z - delta;
+        let real_width = 2. * (fov.to_radians() / 2.).tan();
+        let delta = real_width / width as f64;
+
+        let right = Point::new(right.x * delta, right.y * delta, right.z * delta);
+        let down = Point::new(-up.x * delta, -up.y * delta, -up.z * delta);
+
+        let half_width = width as f64 / 2.;
+        let half_height = height as f64 / 2.;
+
+        let origin = Point::new(
+            camera_pos.x + forward.x - right.x * half_width - down.x * half_height,
+            camera_pos.y + forward.y - right.y * half_width - down.y * half_height,
+            camera_pos.z + forward.z - right.z * half_width - down.z * half_height,
+        );
+
+        Canvas { origin, right, down }
     }
 
-    canvas
-}
\ No newline at end of file
+    // angle covered by one pixel near the image center
+    pub fn pixel_spread(&self) -> f64 {
+        (self.right.x * self.right.x + self.right.y * self.right.y + self.right.z * self.right.z).sqrt()
+    }
+
+    pub fn point(&self, x: f64, y: f64) -> Point {
+        Point::new(
+            self.origin.x + self.right.x * x + self.down.x * y,
+            self.origin.y + self.right.y * x + self.down.y * y,
+            self.origin.z + self.right.z * x + self.down.z * y,
+        )
+    }
+}
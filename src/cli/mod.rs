@@ -0,0 +1,779 @@
+use std::path::{Path,PathBuf};
+use std::time::Duration;
+use crate::geometry::Point;
+use crate::output::{Compression,Depth,Format,Settings};
+use crate::tonemap::{DisplayTransform,Operator,Transfer};
+use crate::integrator::Settings as IntegratorSettings;
+use crate::texture::Filter;
+use crate::color::Color;
+use crate::aov::{self,Aov};
+use crate::aov::cryptomatte::Matte;
+use crate::environment::{Settings as EnvironmentSettings,Source,solar_position};
+use crate::tracing::{Adaptive,Checkpoint,Progressive};
+use crate::denoise::Settings as DenoiseSettings;
+use crate::loader::stl::Settings as StlSettings;
+
+pub const USAGE: &str = "\
+Usage: trace [OPTIONS] --input <FILE>
+
+Options:
+  -i, --input <FILE>       Mesh to render, OBJ or STL (ASCII or binary)
+  -o, --output <FILE>      Image to write [default: input name with format extension]
+  -f, --format <FORMAT>    Output format: bmp, png, ppm, pgm, tga, hdr, exr [default: from --output]
+      --depth <BITS>       Bits per channel: 8 or 16 (png, ppm, pgm), 16 or 32 (exr)
+      --alpha              Write coverage as an alpha channel (png, tga, exr)
+      --ascii              Write plain-text PPM/PGM
+      --compression <C>    EXR compression: none, zip [default: none]
+      --exposure <STOPS>   Exposure adjustment before tone mapping [default: 0]
+      --tonemap <OP>       none, reinhard, reinhard-extended, aces, hable [default: none]
+      --white-point <L>    Luminance mapped to white by reinhard-extended and hable
+      --transfer <T>       Display encoding: srgb, linear or a gamma value [default: srgb]
+                           --exposure, --tonemap, --white-point and --transfer
+                           shape display formats only; hdr and exr stay linear
+  -W, --width <PIXELS>     Image width [default: 640]
+  -H, --height <PIXELS>    Image height [default: 480]
+  -r, --resolution <WxH>   Image width and height, e.g. 1280x720
+      --camera <X,Y,Z>     Camera position [default: 0,-3,0]
+      --look-at <X,Y,Z>    Point the camera looks at [default: 0,0,0]
+      --fov <DEGREES>      Horizontal field of view [default: 22.6]
+      --light <X,Y,Z>      Point light position [default: 1.5,-1.5,1.5]
+      --light-power <W>    Point light intensity [default: 1]
+      --integrator <NAME>  flat, direct, whitted, path, ao [default: whitted]
+      --max-depth <N>      Maximum bounces (whitted, path) [default: 5]
+      --roulette-depth <N> Bounces before Russian roulette starts (path) [default: 3]
+      --ao-samples <N>     Occlusion rays per hit (ao) [default: 16]
+      --ao-distance <D>    Occlusion search distance (ao) [default: unlimited]
+      --ao-pass <FILE>     Also write an ambient occlusion image, using the
+                           --ao-* settings
+      --aov <NAMES>        Comma-separated per-pixel data to write as well: depth,
+                           normal, albedo, position, triangle, object, material,
+                           uv, mask or all. EXR output holds them as layers,
+                           other formats get one image each
+      --aov-separate       Write AOVs as separate images even for EXR output
+      --cryptomatte <NAMES>
+                           Comma-separated ID mattes to write: object (from
+                           OBJ o and g lines or STL solids), material or all. EXR output
+                           holds them, other formats get IMAGE.cryptomatte.exr
+      --cryptomatte-ranks <N>
+                           IDs kept per pixel [default: 6]
+      --stl-normals        Flip STL facets whose winding disagrees with their
+                           stored normal
+      --weld-tolerance <D> Distance under which STL corners snap to one position
+                           [default: a millionth of the model size]
+      --texture-filter <F> bilinear, trilinear, anisotropic [default: anisotropic]
+      --environment <ENV>  Environment light and background: an equirectangular
+                           .hdr, .png or .bmp image, a color R,G,B,
+                           gradient:ZENITH:HORIZON[:GROUND] with R,G,B colors
+                           or sky for daylight with a sun [default: hidden white]
+      --environment-intensity <S>
+                           Environment radiance multiplier [default: 1]
+      --environment-rotation <DEGREES>
+                           Turns the environment around +z [default: 0]
+      --turbidity <T>      Sky haziness, from 2 (clear) to 10 (hazy) [default: 3]
+      --sun-elevation <DEGREES>
+                           Sun height above the horizon [default: 45]
+      --sun-azimuth <DEGREES>
+                           Sun bearing clockwise from +y (north) [default: 135]
+      --sun-date <DATE>    Places the sun by date, YYYY-MM-DD or MM-DD
+                           [default: 06-21]
+      --sun-time <HH:MM>   Places the sun by local solar time [default: 12:00]
+      --latitude <DEGREES> Places the sun by latitude [default: 45]
+      --hide-environment   Keep the environment out of camera rays, leaving
+                           misses transparent
+  -s, --samples <N>        Samples per pixel, the most any pixel takes when
+                           adaptive [default: 1, 1024 with --adaptive,
+                           unlimited with --time-limit or --noise-threshold]
+      --adaptive <E>       Stop sampling pixels once their relative noise and
+                           their neighbors' is below E
+      --min-samples <N>    Samples every pixel takes before adaptive sampling
+                           judges it [default: 8]
+      --sample-heatmap <FILE>
+                           Also write an image of the samples each pixel took
+      --denoise            Filter the noise out of the image, guided by its
+                           albedo, normals and depth
+      --denoise-radius <PIXELS>
+                           Farthest neighbor the denoiser looks at [default: 7]
+      --denoise-strength <K>
+                           Higher smooths more, lower keeps more detail
+                           [default: 0.7]
+      --keep-noisy         Also write the image before denoising as
+                           IMAGE.noisy.EXT
+      --progressive        Render in passes, writing the image so far as it goes
+      --pass-samples <N>   Samples per pixel in each pass [default: 1]
+      --time-limit <SECONDS>
+                           Stop rendering passes when time runs out
+      --noise-threshold <E>
+                           Stop once the estimated relative noise drops below E
+      --progress-interval <SECONDS>
+                           Time between writes of the image so far [default: 10]
+      --checkpoint <FILE>  Save the render state to FILE as it goes, so an
+                           interrupted render can be resumed
+      --checkpoint-interval <SECONDS>
+                           Time between checkpoints [default: 60]
+      --resume             Continue from the --checkpoint file, which must
+                           come from the same scene and settings
+      --seed <N>           Random seed; a seed always gives the same image
+                           [default: 0]
+  -t, --threads <N>        Render threads [default: available cores]
+  -h, --help               Print this help
+";
+
+#[derive(Debug)]
+pub struct Options {
+    pub input: PathBuf,
+    pub stl: StlSettings,
+    pub output: PathBuf,
+    pub image: Settings,
+    pub width: u32,
+    pub height: u32,
+    pub camera: Point,
+    pub look_at: Point,
+    pub fov: f64,
+    pub light: Point,
+    pub light_power: f64,
+    pub integrator: IntegratorSettings,
+    pub texture_filter: Filter,
+    pub environment: EnvironmentSettings,
+    pub passes: Vec<Pass>,
+    pub aovs: Vec<Aov>,
+    pub aov_separate: bool,
+    pub cryptomatte: Vec<Matte>,
+    pub cryptomatte_ranks: usize,
+    pub samples: u32,
+    pub progressive: Option<Progressive>,
+    pub adaptive: Option<Adaptive>,
+    // where to write the samples per pixel, and how
+    pub heatmap: Option<(PathBuf, Settings)>,
+    pub denoise: Option<DenoiseSettings>,
+    pub checkpoint: Option<Checkpoint>,
+    pub seed: u64,
+    pub threads: usize,
+}
+
+// an extra image rendered with its own integrator after the main one
+#[derive(Debug)]
+pub struct Pass {
+    pub output: PathBuf,
+    pub image: Settings,
+    pub integrator: IntegratorSettings,
+}
+
+pub enum Command {
+    Render(Box<Options>),
+    Help,
+}
+
+fn value<'a>(
+    flag: &str,
+    args: &mut impl Iterator<Item = &'a String>,
+) -> Result<&'a str, String> {
+    args.next()
+        .map(|value| value.as_str())
+        .ok_or_else(|| format!("Missing value for {}", flag))
+}
+
+fn parse_positive<T>(flag: &str, value: &str) -> Result<T, String>
+where
+    T: std::str::FromStr + PartialOrd + Default,
+{
+    match value.parse::<T>() {
+        Ok(number) if number > T::default() => Ok(number),
+        _ => Err(format!("{} expects a positive integer, got '{}'", flag, value)),
+    }
+}
+
+fn parse_count<T: std::str::FromStr>(flag: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("{} expects a non-negative integer, got '{}'", flag, value))
+}
+
+fn parse_float(flag: &str, value: &str) -> Result<f64, String> {
+    value
+        .parse()
+        .map_err(|_| format!("{} expects a number, got '{}'", flag, value))
+}
+
+fn parse_duration(flag: &str, value: &str) -> Result<Duration, String> {
+    let seconds = parse_float(flag, value)?;
+    if !seconds.is_finite() || seconds < 0. {
+        return Err(format!("{} expects a number of seconds, got '{}'", flag, value));
+    }
+    Ok(Duration::from_secs_f64(seconds))
+}
+
+fn parse_point(flag: &str, value: &str) -> Result<Point, String> {
+    let coords: Vec<&str> = value.split(',').collect();
+
+    if coords.len() != 3 {
+        return Err(format!("{} expects X,Y,Z, got '{}'", flag, value));
+    }
+
+    Ok(Point::new(
+        parse_float(flag, coords[0].trim())?,
+        parse_float(flag, coords[1].trim())?,
+        parse_float(flag, coords[2].trim())?,
+    ))
+}
+
+fn parse_resolution(flag: &str, value: &str) -> Result<(u32, u32), String> {
+    let dims: Vec<&str> = value.split('x').collect();
+
+    if dims.len() != 2 {
+        return Err(format!("{} expects WIDTHxHEIGHT, got '{}'", flag, value));
+    }
+
+    Ok((parse_positive(flag, dims[0])?, parse_positive(flag, dims[1])?))
+}
+
+fn parse_format(flag: &str, value: &str) -> Result<Format, String> {
+    Format::from_name(value)
+        .ok_or_else(|| format!("{} does not support '{}'", flag, value))
+}
+
+fn parse_depth(flag: &str, value: &str) -> Result<Depth, String> {
+    match value {
+        "8" => Ok(Depth::Eight),
+        "16" => Ok(Depth::Sixteen),
+        "32" => Ok(Depth::ThirtyTwo),
+        _ => Err(format!("{} expects 8, 16 or 32, got '{}'", flag, value)),
+    }
+}
+
+fn parse_compression(flag: &str, value: &str) -> Result<Compression, String> {
+    match value.to_lowercase().as_str() {
+        "none" => Ok(Compression::None),
+        "zip" => Ok(Compression::Zip),
+        _ => Err(format!("{} expects none or zip, got '{}'", flag, value)),
+    }
+}
+
+fn parse_operator(flag: &str, value: &str) -> Result<Operator, String> {
+    Operator::from_name(value)
+        .ok_or_else(|| format!("{} does not support '{}'", flag, value))
+}
+
+fn parse_transfer(flag: &str, value: &str) -> Result<Transfer, String> {
+    Transfer::from_name(value)
+        .ok_or_else(|| format!("{} expects srgb, linear or a positive gamma, got '{}'", flag, value))
+}
+
+fn parse_filter(flag: &str, value: &str) -> Result<Filter, String> {
+    Filter::from_name(value)
+        .ok_or_else(|| format!("{} does not support '{}'", flag, value))
+}
+
+fn parse_color(flag: &str, value: &str) -> Result<Color, String> {
+    let channels = value
+        .split(',')
+        .map(|channel| channel.trim().parse::<f64>().ok().filter(|channel| *channel >= 0.))
+        .collect::<Option<Vec<_>>>();
+
+    match channels.as_deref() {
+        Some(&[r, g, b]) => Ok(Color::new(r, g, b)),
+        _ => Err(format!("{} expects non-negative R,G,B, got '{}'", flag, value)),
+    }
+}
+
+fn parse_environment(flag: &str, value: &str) -> Result<Source, String> {
+    if value == "sky" {
+        // the sun is placed once all options are known
+        return Ok(Source::Sky { turbidity: 3., elevation: 45., azimuth: 135. });
+    }
+
+    if let Some(colors) = value.strip_prefix("gradient:") {
+        let colors = colors
+            .split(':')
+            .map(|color| parse_color(flag, color))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        return match colors[..] {
+            [zenith, horizon] => Ok(Source::Gradient { zenith, horizon, ground: horizon }),
+            [zenith, horizon, ground] => Ok(Source::Gradient { zenith, horizon, ground }),
+            _ => Err(format!("{} expects gradient:ZENITH:HORIZON[:GROUND], got '{}'", flag, value)),
+        };
+    }
+
+    match parse_color(flag, value) {
+        Ok(color) => Ok(Source::Constant(color)),
+        Err(_) => Ok(Source::Image(PathBuf::from(value))),
+    }
+}
+
+fn parse_angle(flag: &str, value: &str, limit: f64) -> Result<f64, String> {
+    let angle = parse_float(flag, value)?;
+    if angle.abs() > limit {
+        return Err(format!("{} must be between -{} and {} degrees", flag, limit, limit));
+    }
+    Ok(angle)
+}
+
+// day of the year, counting leap days only when the year is given
+fn parse_date(flag: &str, value: &str) -> Result<u32, String> {
+    let error = || format!("{} expects YYYY-MM-DD or MM-DD, got '{}'", flag, value);
+    let parts = value
+        .split('-')
+        .map(|part| part.parse::<u32>().ok())
+        .collect::<Option<Vec<_>>>()
+        .ok_or_else(error)?;
+
+    let (leap, month, day) = match parts[..] {
+        [year, month, day] => ((year % 4 == 0 && year % 100 != 0) || year % 400 == 0, month, day),
+        [month, day] => (false, month, day),
+        _ => return Err(error()),
+    };
+
+    let lengths = [31, if leap { 29 } else { 28 }, 31, 30, 31, 30, 31, 31, 30, 31, 30, 31];
+    if month == 0 || month > 12 || day == 0 || day > lengths[month as usize - 1] {
+        return Err(error());
+    }
+
+    Ok(lengths[..month as usize - 1].iter().sum::<u32>() + day)
+}
+
+// hours since midnight from HH:MM or a decimal hour
+fn parse_time(flag: &str, value: &str) -> Result<f64, String> {
+    let error = || format!("{} expects HH:MM, got '{}'", flag, value);
+    let hours = match value.split_once(':') {
+        Some((hours, minutes)) => {
+            let hours: u32 = hours.parse().map_err(|_| error())?;
+            let minutes: u32 = minutes.parse().map_err(|_| error())?;
+            if minutes >= 60 {
+                return Err(error());
+            }
+            hours as f64 + minutes as f64 / 60.
+        }
+        None => value.parse().map_err(|_| error())?,
+    };
+
+    if !(0. ..=24.).contains(&hours) {
+        return Err(error());
+    }
+    Ok(hours)
+}
+
+// a pass keeps the main image settings but takes its format from its own
+// file name, falling back to the format's own bit depth and alpha support
+fn pass_image(flag: &str, path: &Path, image: &Settings) -> Result<Settings, String> {
+    let format = match path.extension() {
+        Some(ext) => parse_format(flag, &ext.to_string_lossy())?,
+        None => image.format,
+    };
+
+    let settings = Settings {
+        format,
+        depth: if format.supports_depth(image.depth) {
+            image.depth
+        } else {
+            format.default_depth()
+        },
+        alpha: image.alpha && format.supports_alpha(),
+        ..image.clone()
+    };
+    settings.validate()?;
+    Ok(settings)
+}
+
+fn parse_aovs(flag: &str, value: &str) -> Result<Vec<Aov>, String> {
+    if value == "all" {
+        return Ok(aov::ALL.to_vec());
+    }
+
+    let mut aovs = vec![];
+    for name in value.split(',').map(str::trim) {
+        let aov = Aov::from_name(name)
+            .ok_or_else(|| format!("{} does not support '{}'", flag, name))?;
+        if !aovs.contains(&aov) {
+            aovs.push(aov);
+        }
+    }
+    Ok(aovs)
+}
+
+fn parse_mattes(flag: &str, value: &str) -> Result<Vec<Matte>, String> {
+    if value == "all" {
+        return Ok(vec![Matte::Object, Matte::Material]);
+    }
+
+    let mut mattes = vec![];
+    for name in value.split(',').map(str::trim) {
+        let matte = Matte::from_name(name)
+            .ok_or_else(|| format!("{} does not support '{}'", flag, name))?;
+        if !mattes.contains(&matte) {
+            mattes.push(matte);
+        }
+    }
+    Ok(mattes)
+}
+
+fn default_threads() -> usize {
+    std::thread::available_parallelism()
+        .map(|count| count.get())
+        .unwrap_or(1)
+}
+
+pub fn parse(args: &[String]) -> Result<Command, String> {
+    let mut input: Option<PathBuf> = None;
+    let mut output: Option<PathBuf> = None;
+    let mut format: Option<Format> = None;
+    let mut depth: Option<Depth> = None;
+    let mut alpha = false;
+    let mut ascii = false;
+    let mut compression = Compression::None;
+    let mut display_flag: Option<&str> = None;
+    let mut sky_flag: Option<&str> = None;
+    let mut display = DisplayTransform::default();
+    let mut width: u32 = 640;
+    let mut height: u32 = 480;
+    let mut camera = Point::new(0., -3., 0.);
+    let mut look_at = Point::new(0., 0., 0.);
+    let mut fov: f64 = 22.6;
+    let mut light = Point::new(1.5, -1.5, 1.5);
+    let mut light_power: f64 = 1.;
+    let mut integrator = "whitted".to_string();
+    let mut max_depth: u32 = 5;
+    let mut roulette_depth: u32 = 3;
+    let mut ao_samples: u32 = 16;
+    let mut ao_distance = f64::INFINITY;
+    let mut ao_pass: Option<PathBuf> = None;
+    let mut aovs: Vec<Aov> = vec![];
+    let mut aov_separate = false;
+    let mut cryptomatte: Vec<Matte> = vec![];
+    let mut cryptomatte_ranks: usize = 6;
+    let mut texture_filter = Filter::Anisotropic;
+    let mut environment = EnvironmentSettings::default();
+    let mut hide_environment = false;
+    let mut turbidity: f64 = 3.;
+    let mut sun_elevation: Option<f64> = None;
+    let mut sun_azimuth: Option<f64> = None;
+    let mut sun_date: Option<u32> = None;
+    let mut sun_time: Option<f64> = None;
+    let mut latitude: Option<f64> = None;
+    let mut samples: Option<u32> = None;
+    let mut progressive: Option<Progressive> = None;
+    let mut adaptive_threshold: Option<f64> = None;
+    let mut min_samples: Option<u32> = None;
+    let mut heatmap: Option<PathBuf> = None;
+    let mut denoise: Option<DenoiseSettings> = None;
+    let mut checkpoint: Option<PathBuf> = None;
+    let mut checkpoint_interval = Duration::from_secs(60);
+    let mut resume = false;
+    let mut seed: u64 = 0;
+    let mut stl = StlSettings::default();
+    let mut threads = default_threads();
+
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        let flag = arg.as_str();
+        if matches!(flag, "--exposure" | "--tonemap" | "--white-point" | "--transfer") {
+            display_flag = Some(flag);
+        }
+        if matches!(
+            flag,
+            "--turbidity" | "--sun-elevation" | "--sun-azimuth" | "--sun-date" | "--sun-time" | "--latitude"
+        ) {
+            sky_flag = Some(flag);
+        }
+
+        match flag {
+            "-h" | "--help" => return Ok(Command::Help),
+            "-i" | "--input" => input = Some(PathBuf::from(value(flag, &mut args)?)),
+            "-o" | "--output" => output = Some(PathBuf::from(value(flag, &mut args)?)),
+            "-f" | "--format" => format = Some(parse_format(flag, value(flag, &mut args)?)?),
+            "--depth" => depth = Some(parse_depth(flag, value(flag, &mut args)?)?),
+            "--alpha" => alpha = true,
+            "--ascii" => ascii = true,
+            "--compression" => compression = parse_compression(flag, value(flag, &mut args)?)?,
+            "--exposure" => display.exposure = parse_float(flag, value(flag, &mut args)?)?,
+            "--tonemap" => display.operator = parse_operator(flag, value(flag, &mut args)?)?,
+            "--white-point" => {
+                let white = parse_float(flag, value(flag, &mut args)?)?;
+                if white <= 0. {
+                    return Err(format!("{} must be positive", flag));
+                }
+                display.white_point = Some(white);
+            }
+            "--transfer" => display.transfer = parse_transfer(flag, value(flag, &mut args)?)?,
+            "-W" | "--width" => width = parse_positive(flag, value(flag, &mut args)?)?,
+            "-H" | "--height" => height = parse_positive(flag, value(flag, &mut args)?)?,
+            "-r" | "--resolution" => {
+                let (w, h) = parse_resolution(flag, value(flag, &mut args)?)?;
+                width = w;
+                height = h;
+            }
+            "--camera" => camera = parse_point(flag, value(flag, &mut args)?)?,
+            "--look-at" => look_at = parse_point(flag, value(flag, &mut args)?)?,
+            "--fov" => {
+                fov = parse_float(flag, value(flag, &mut args)?)?;
+                if fov <= 0. || fov >= 180. {
+                    return Err(format!("{} must be between 0 and 180 degrees", flag));
+                }
+            }
+            "--light" => light = parse_point(flag, value(flag, &mut args)?)?,
+            "--light-power" => {
+                light_power = parse_float(flag, value(flag, &mut args)?)?;
+                if light_power < 0. {
+                    return Err(format!("{} must not be negative", flag));
+                }
+            }
+            "--integrator" => integrator = value(flag, &mut args)?.to_lowercase(),
+            "--max-depth" => max_depth = parse_count(flag, value(flag, &mut args)?)?,
+            "--roulette-depth" => roulette_depth = parse_count(flag, value(flag, &mut args)?)?,
+            "--ao-samples" => ao_samples = parse_positive(flag, value(flag, &mut args)?)?,
+            "--ao-distance" => {
+                ao_distance = parse_float(flag, value(flag, &mut args)?)?;
+                if ao_distance <= 0. {
+                    return Err(format!("{} must be positive", flag));
+                }
+            }
+            "--ao-pass" => ao_pass = Some(PathBuf::from(value(flag, &mut args)?)),
+            "--aov" => aovs = parse_aovs(flag, value(flag, &mut args)?)?,
+            "--aov-separate" => aov_separate = true,
+            "--cryptomatte" => cryptomatte = parse_mattes(flag, value(flag, &mut args)?)?,
+            "--cryptomatte-ranks" => cryptomatte_ranks = parse_positive(flag, value(flag, &mut args)?)?,
+            "--stl-normals" => stl.facet_normals = true,
+            "--weld-tolerance" => {
+                let tolerance = parse_float(flag, value(flag, &mut args)?)?;
+                if tolerance <= 0. {
+                    return Err(format!("{} must be positive", flag));
+                }
+                stl.weld_tolerance = Some(tolerance);
+            }
+            "--texture-filter" => texture_filter = parse_filter(flag, value(flag, &mut args)?)?,
+            "--environment" => {
+                environment.source = parse_environment(flag, value(flag, &mut args)?)?;
+                environment.visible = true;
+            }
+            "--environment-intensity" => {
+                environment.intensity = parse_float(flag, value(flag, &mut args)?)?;
+                if environment.intensity < 0. {
+                    return Err(format!("{} must not be negative", flag));
+                }
+            }
+            "--environment-rotation" => {
+                environment.rotation = parse_float(flag, value(flag, &mut args)?)?;
+            }
+            "--hide-environment" => hide_environment = true,
+            "--turbidity" => {
+                turbidity = parse_float(flag, value(flag, &mut args)?)?;
+                if !(1.7..=10.).contains(&turbidity) {
+                    return Err(format!("{} must be between 1.7 and 10", flag));
+                }
+            }
+            "--sun-elevation" => sun_elevation = Some(parse_angle(flag, value(flag, &mut args)?, 90.)?),
+            "--sun-azimuth" => sun_azimuth = Some(parse_float(flag, value(flag, &mut args)?)?),
+            "--sun-date" => sun_date = Some(parse_date(flag, value(flag, &mut args)?)?),
+            "--sun-time" => sun_time = Some(parse_time(flag, value(flag, &mut args)?)?),
+            "--latitude" => latitude = Some(parse_angle(flag, value(flag, &mut args)?, 90.)?),
+            "-s" | "--samples" => samples = Some(parse_positive(flag, value(flag, &mut args)?)?),
+            "--progressive" => {
+                progressive.get_or_insert_with(Progressive::default);
+            }
+            "--pass-samples" => {
+                let count = parse_positive(flag, value(flag, &mut args)?)?;
+                progressive.get_or_insert_with(Progressive::default).pass_samples = count;
+            }
+            "--time-limit" => {
+                let limit = parse_duration(flag, value(flag, &mut args)?)?;
+                progressive.get_or_insert_with(Progressive::default).time_limit = Some(limit);
+            }
+            "--noise-threshold" => {
+                let threshold = parse_float(flag, value(flag, &mut args)?)?;
+                if threshold <= 0. {
+                    return Err(format!("{} must be positive", flag));
+                }
+                progressive.get_or_insert_with(Progressive::default).noise_threshold = Some(threshold);
+            }
+            "--adaptive" => {
+                let threshold = parse_float(flag, value(flag, &mut args)?)?;
+                if threshold <= 0. {
+                    return Err(format!("{} must be positive", flag));
+                }
+                adaptive_threshold = Some(threshold);
+            }
+            "--min-samples" => min_samples = Some(parse_positive(flag, value(flag, &mut args)?)?),
+            "--sample-heatmap" => heatmap = Some(PathBuf::from(value(flag, &mut args)?)),
+            "--denoise" => {
+                denoise.get_or_insert_with(DenoiseSettings::default);
+            }
+            "--denoise-radius" => {
+                let radius = parse_positive(flag, value(flag, &mut args)?)?;
+                denoise.get_or_insert_with(DenoiseSettings::default).radius = radius;
+            }
+            "--denoise-strength" => {
+                let strength = parse_float(flag, value(flag, &mut args)?)?;
+                if strength <= 0. {
+                    return Err(format!("{} must be positive", flag));
+                }
+                denoise.get_or_insert_with(DenoiseSettings::default).strength = strength;
+            }
+            "--keep-noisy" => denoise.get_or_insert_with(DenoiseSettings::default).keep_noisy = true,
+            "--checkpoint" => checkpoint = Some(PathBuf::from(value(flag, &mut args)?)),
+            "--checkpoint-interval" => checkpoint_interval = parse_duration(flag, value(flag, &mut args)?)?,
+            "--resume" => resume = true,
+            "--progress-interval" => {
+                let interval = parse_duration(flag, value(flag, &mut args)?)?;
+                progressive.get_or_insert_with(Progressive::default).interval = interval;
+            }
+            "--seed" => seed = parse_count(flag, value(flag, &mut args)?)?,
+            "-t" | "--threads" => threads = parse_positive(flag, value(flag, &mut args)?)?,
+            _ => return Err(format!("Unknown option '{}'", arg)),
+        }
+    }
+
+    let input = input.ok_or("Missing required option --input")?;
+
+    // a time or noise budget decides when to stop unless samples are capped too
+    let budgeted = progressive
+        .as_ref()
+        .is_some_and(|progressive| progressive.time_limit.is_some() || progressive.noise_threshold.is_some());
+    if min_samples.is_some() && adaptive_threshold.is_none() {
+        return Err("--min-samples needs --adaptive".to_string());
+    }
+    let adaptive = adaptive_threshold.map(|threshold| Adaptive {
+        threshold,
+        min_samples: min_samples.unwrap_or(8),
+    });
+
+    let samples = samples.unwrap_or(match (&adaptive, budgeted) {
+        (Some(_), _) => 1024,
+        (None, true) => u32::MAX,
+        (None, false) => 1,
+    });
+
+    if hide_environment {
+        environment.visible = false;
+    }
+
+    let by_angles = sun_elevation.is_some() || sun_azimuth.is_some();
+    let by_date = sun_date.is_some() || sun_time.is_some() || latitude.is_some();
+
+    if by_angles && by_date {
+        return Err("Place the sun either by elevation and azimuth or by date, time and latitude".to_string());
+    }
+
+    if let Some(flag) = sky_flag.filter(|_| !matches!(environment.source, Source::Sky { .. })) {
+        return Err(format!("{} needs --environment sky", flag));
+    }
+
+    if let Source::Sky { turbidity: sky_turbidity, elevation, azimuth } = &mut environment.source {
+        *sky_turbidity = turbidity;
+
+        if by_date {
+            let (sun_elevation, sun_azimuth) = solar_position(
+                sun_date.unwrap_or(172),
+                sun_time.unwrap_or(12.),
+                latitude.unwrap_or(45.).to_radians(),
+            );
+            *elevation = sun_elevation.to_degrees();
+            *azimuth = sun_azimuth.to_degrees();
+        } else {
+            *elevation = sun_elevation.unwrap_or(45.);
+            *azimuth = sun_azimuth.unwrap_or(135.);
+        }
+    }
+
+    if camera == look_at {
+        return Err("--camera and --look-at must be different points".to_string());
+    }
+
+    let format = match (format, &output) {
+        (Some(format), _) => format,
+        (None, Some(path)) => match path.extension() {
+            Some(ext) => parse_format("--output", &ext.to_string_lossy())?,
+            None => Format::Bmp,
+        },
+        (None, None) => Format::Bmp,
+    };
+    if let Some(flag) = display_flag.filter(|_| format.is_linear()) {
+        return Err(format!("{} does not apply to {} output, which stays linear", flag, format.extension()));
+    }
+    if display.white_point.is_some() && !matches!(display.operator, Operator::ExtendedReinhard | Operator::Hable) {
+        return Err("--white-point needs --tonemap reinhard-extended or hable".to_string());
+    }
+
+    let ambient_occlusion = IntegratorSettings::AmbientOcclusion {
+        samples: ao_samples,
+        distance: ao_distance,
+    };
+
+    let integrator = match integrator.as_str() {
+        "flat" => IntegratorSettings::Flat,
+        "direct" => IntegratorSettings::Direct,
+        "whitted" => IntegratorSettings::Whitted { max_depth },
+        "path" => IntegratorSettings::Path { max_depth, roulette_depth },
+        "ao" => ambient_occlusion.clone(),
+        name => return Err(format!("--integrator does not support '{}'", name)),
+    };
+
+    let image = Settings {
+        format,
+        depth: depth.unwrap_or_else(|| format.default_depth()),
+        alpha,
+        ascii,
+        compression,
+        display,
+    };
+    image.validate()?;
+
+    let mut passes = vec![];
+
+    if let Some(path) = ao_pass {
+        passes.push(Pass {
+            image: pass_image("--ao-pass", &path, &image)?,
+            output: path,
+            integrator: ambient_occlusion,
+        });
+    }
+
+    if resume && checkpoint.is_none() {
+        return Err("--resume needs --checkpoint".to_string());
+    }
+    let checkpoint = checkpoint.map(|path| Checkpoint {
+        path,
+        interval: checkpoint_interval,
+        resume,
+    });
+
+    let heatmap = match heatmap {
+        Some(path) => Some((path.clone(), pass_image("--sample-heatmap", &path, &image)?.for_data())),
+        None => None,
+    };
+
+    let output = output.unwrap_or_else(|| {
+        let mut path = PathBuf::from(input.file_name().unwrap_or_default());
+        path.set_extension(format.extension());
+        path
+    });
+
+    Ok(Command::Render(Box::new(Options {
+        input,
+        stl,
+        output,
+        image,
+        width,
+        height,
+        camera,
+        look_at,
+        fov,
+        light,
+        light_power,
+        integrator,
+        texture_filter,
+        environment,
+        passes,
+        aovs,
+        aov_separate,
+        cryptomatte,
+        cryptomatte_ranks,
+        samples,
+        progressive,
+        adaptive,
+        heatmap,
+        denoise,
+        checkpoint,
+        seed,
+        threads,
+    })))
+}
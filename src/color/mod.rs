@@ -0,0 +1,61 @@
+use std::ops::{Add,AddAssign,Mul};
+
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub struct Color {
+    pub r: f64,
+    pub g: f64,
+    pub b: f64,
+}
+
+impl Color {
+    pub fn new(r: f64, g: f64, b: f64) -> Self {
+        Color { r, g, b }
+    }
+
+    pub fn gray(value: f64) -> Self {
+        Color::new(value, value, value)
+    }
+
+    pub fn luminance(&self) -> f64 {
+        0.2126 * self.r + 0.7152 * self.g + 0.0722 * self.b
+    }
+
+    pub fn map<F: Fn(f64) -> f64>(&self, f: F) -> Self {
+        Color::new(f(self.r), f(self.g), f(self.b))
+    }
+}
+
+impl Add for Color {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Color::new(self.r + other.r, self.g + other.g, self.b + other.b)
+    }
+}
+
+impl AddAssign for Color {
+    fn add_assign(&mut self, other: Self) {
+        self.r += other.r;
+        self.g += other.g;
+        self.b += other.b;
+    }
+}
+
+impl Mul for Color {
+    type Output = Self;
+
+    fn mul(self, other: Self) -> Self {
+        Color::new(self.r * other.r, self.g * other.g, self.b * other.b)
+    }
+}
+
+impl Mul<f64> for Color {
+    type Output = Self;
+
+    fn mul(self, n: f64) -> Self {
+        Color::new(self.r * n, self.g * n, self.b * n)
+    }
+}
+
+pub const BLACK: Color = Color { r: 0., g: 0., b: 0. };
+pub const WHITE: Color = Color { r: 1., g: 1., b: 1. };
@@ -0,0 +1,204 @@
+use std::thread;
+use crate::aov::Aov;
+use crate::color::{Color,BLACK};
+use crate::framebuffer::Framebuffer;
+use crate::tracing::Render;
+
+// first-hit data steering the filter, rendered whenever it runs
+pub const GUIDES: [Aov; 4] = [Aov::Albedo, Aov::Normal, Aov::Depth, Aov::Mask];
+
+// how far neighbors may differ before they stop counting
+const NORMAL_SIGMA: f64 = 0.3;
+const ALBEDO_SIGMA: f64 = 0.1;
+// relative to the depth of the pixel being filtered
+const DEPTH_SIGMA: f64 = 0.05;
+const MASK_SIGMA: f64 = 0.1;
+
+// darker albedo is not divided out any further, so black surfaces don't blow up
+const ALBEDO_FLOOR: f64 = 0.01;
+// keeps the patch distance finite where both pixels are noiseless
+const VARIANCE_EPSILON: f64 = 1e-10;
+const PATCH_RADIUS: i64 = 1;
+
+// a joint non-local means filter: neighbors within `radius` pixels are
+// averaged when their surroundings look alike given the pixel noise, and
+// `strength` scales how alike that must be
+#[derive(Debug,Clone)]
+pub struct Settings {
+    pub radius: u32,
+    pub strength: f64,
+    // also write the image as rendered, next to the denoised one
+    pub keep_noisy: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            radius: 7,
+            strength: 0.7,
+            keep_noisy: false,
+        }
+    }
+}
+
+struct Guide {
+    albedo: Color,
+    normal: Color,
+    depth: f64,
+    mask: f64,
+}
+
+impl Guide {
+    fn distance(&self, other: &Guide) -> f64 {
+        let squared = |a: Color, b: Color| (a.r - b.r).powi(2) + (a.g - b.g).powi(2) + (a.b - b.b).powi(2);
+        let depth = (self.depth - other.depth) / self.depth.abs().max(1e-6);
+
+        squared(self.normal, other.normal) / (2. * NORMAL_SIGMA * NORMAL_SIGMA)
+            + squared(self.albedo, other.albedo) / (2. * ALBEDO_SIGMA * ALBEDO_SIGMA)
+            + depth * depth / (2. * DEPTH_SIGMA * DEPTH_SIGMA)
+            + (self.mask - other.mask).powi(2) / (2. * MASK_SIGMA * MASK_SIGMA)
+    }
+}
+
+// the image split into lighting and albedo, so the filter smooths the light
+// without blurring textures
+struct Image {
+    width: i64,
+    height: i64,
+    guides: Vec<Guide>,
+    albedo: Vec<Color>,
+    light: Vec<Color>,
+    variance: Vec<Color>,
+}
+
+impl Image {
+    fn new(render: &Render) -> Result<Self, String> {
+        let layer = |aov: Aov| {
+            render
+                .layers
+                .iter()
+                .find(|layer| layer.name == aov.name())
+                .map(|layer| &layer.framebuffer.pixels)
+                .ok_or_else(|| format!("denoising needs the {} AOV", aov.name()))
+        };
+        let (albedo, normal, depth, mask) = (layer(Aov::Albedo)?, layer(Aov::Normal)?, layer(Aov::Depth)?, layer(Aov::Mask)?);
+
+        let guides: Vec<Guide> = (0..albedo.len())
+            .map(|index| Guide {
+                albedo: albedo[index],
+                normal: normal[index],
+                depth: depth[index].r,
+                mask: mask[index].r,
+            })
+            .collect();
+        let albedo: Vec<Color> = albedo.iter().map(|albedo| albedo.map(|value| value.max(ALBEDO_FLOOR))).collect();
+        let light = render
+            .framebuffer
+            .pixels
+            .iter()
+            .zip(&albedo)
+            .map(|(color, albedo)| Color::new(color.r / albedo.r, color.g / albedo.g, color.b / albedo.b))
+            .collect();
+        let variance = render
+            .variance
+            .iter()
+            .zip(&albedo)
+            .map(|(variance, albedo)| {
+                Color::new(variance.r / albedo.r.powi(2), variance.g / albedo.g.powi(2), variance.b / albedo.b.powi(2))
+            })
+            .collect();
+
+        Ok(Image {
+            width: render.framebuffer.width as i64,
+            height: render.framebuffer.height as i64,
+            guides,
+            albedo,
+            light,
+            variance,
+        })
+    }
+
+    fn index(&self, x: i64, y: i64) -> usize {
+        (y.clamp(0, self.height - 1) * self.width + x.clamp(0, self.width - 1)) as usize
+    }
+
+    // mean squared difference of the patches around two pixels, less what
+    // their noise alone explains; zero or below means they are alike
+    fn patch_distance(&self, (x, y): (i64, i64), (qx, qy): (i64, i64), strength: f64) -> f64 {
+        let mut total = 0.;
+        let mut count = 0.;
+
+        for dy in -PATCH_RADIUS..=PATCH_RADIUS {
+            for dx in -PATCH_RADIUS..=PATCH_RADIUS {
+                let p = self.index(x + dx, y + dy);
+                let q = self.index(qx + dx, qy + dy);
+                let channels = |color: Color| [color.r, color.g, color.b];
+
+                for (((a, b), vp), vq) in channels(self.light[p])
+                    .iter()
+                    .zip(channels(self.light[q]))
+                    .zip(channels(self.variance[p]))
+                    .zip(channels(self.variance[q]))
+                {
+                    let noise = vp + vp.min(vq);
+                    let scale = VARIANCE_EPSILON + strength * strength * (vp + vq);
+                    total += ((a - b).powi(2) - noise) / scale;
+                    count += 1.;
+                }
+            }
+        }
+
+        total / count
+    }
+
+    fn filter(&self, x: i64, y: i64, settings: &Settings) -> Color {
+        let radius = settings.radius as i64;
+        let spatial = 2. * (settings.radius as f64 / 2.).max(0.5).powi(2);
+        let center = self.index(x, y);
+        // one sample per pixel leaves no variance to judge the patches by
+        let noise_known = self.variance[center].r.is_finite();
+
+        let mut total = BLACK;
+        let mut weights = 0.;
+
+        for qy in (y - radius).max(0)..=(y + radius).min(self.height - 1) {
+            for qx in (x - radius).max(0)..=(x + radius).min(self.width - 1) {
+                let neighbor = self.index(qx, qy);
+                let mut distance = ((qx - x).pow(2) + (qy - y).pow(2)) as f64 / spatial
+                    + self.guides[center].distance(&self.guides[neighbor]);
+
+                if noise_known && self.variance[neighbor].r.is_finite() {
+                    distance += self.patch_distance((x, y), (qx, qy), settings.strength).max(0.);
+                }
+
+                let weight = (-distance).exp();
+                total += self.light[neighbor] * weight;
+                weights += weight;
+            }
+        }
+
+        total * (1. / weights) * self.albedo[center]
+    }
+}
+
+// filters the image rows in parallel; alpha is kept as rendered
+pub fn denoise(render: &Render, settings: &Settings, threads: usize) -> Result<Framebuffer, String> {
+    let image = Image::new(render)?;
+    let mut framebuffer = render.framebuffer.clone();
+    let width = image.width as usize;
+    let rows_per_thread = (image.height as usize).div_ceil(threads.max(1)).max(1);
+
+    thread::scope(|scope| {
+        for (block, pixels) in framebuffer.pixels.chunks_mut(rows_per_thread * width).enumerate() {
+            let image = &image;
+            scope.spawn(move || {
+                for (offset, pixel) in pixels.iter_mut().enumerate() {
+                    let index = block * rows_per_thread * width + offset;
+                    *pixel = image.filter((index % width) as i64, (index / width) as i64, settings);
+                }
+            });
+        }
+    });
+
+    Ok(framebuffer)
+}
@@ -0,0 +1,159 @@
+use std::f64::consts::PI;
+use crate::color::Color;
+use crate::geometry::Vector;
+use crate::sampling::Distribution;
+use super::{Environment,Sample};
+
+// latitude-longitude map importance sampled by luminance; rows near the poles
+// cover less solid angle, so their weights are scaled by sin(theta)
+pub struct Equirectangular {
+    width: usize,
+    height: usize,
+    texels: Vec<Color>,
+    intensity: f64,
+    rotation: f64,
+    rows: Distribution,
+    columns: Vec<Distribution>,
+}
+
+impl Equirectangular {
+    pub fn new(width: usize, height: usize, texels: Vec<Color>, intensity: f64, rotation: f64) -> Self {
+        let columns: Vec<Distribution> = (0..height)
+            .map(|y| {
+                let sin = (PI * (y as f64 + 0.5) / height as f64).sin();
+                let row = &texels[y * width..(y + 1) * width];
+                Distribution::new(row.iter().map(|texel| texel.luminance().max(0.) * sin).collect())
+            })
+            .collect();
+        let rows = Distribution::new(columns.iter().map(|column| column.integral).collect());
+
+        Equirectangular { width, height, texels, intensity, rotation, rows, columns }
+    }
+
+    // the image center looks down +x; u grows clockwise seen from above so
+    // the panorama is not mirrored from inside
+    fn position(&self, direction: &Vector) -> (f64, f64) {
+        let phi = direction.y.atan2(direction.x) - self.rotation;
+        let u = (0.5 - phi / (2. * PI)).rem_euclid(1.);
+        let v = direction.z.clamp(-1., 1.).acos() / PI;
+        (u, v)
+    }
+
+    fn direction(&self, u: f64, v: f64) -> Vector<'static> {
+        let phi = (0.5 - u) * 2. * PI + self.rotation;
+        let theta = v * PI;
+        Vector::new(theta.sin() * phi.cos(), theta.sin() * phi.sin(), theta.cos())
+    }
+
+    fn texel(&self, x: i64, y: i64) -> Color {
+        let x = x.rem_euclid(self.width as i64) as usize;
+        let y = y.clamp(0, self.height as i64 - 1) as usize;
+        self.texels[y * self.width + x]
+    }
+
+    fn cell(&self, u: f64, v: f64) -> (usize, usize) {
+        let x = ((u * self.width as f64) as usize).min(self.width - 1);
+        let y = ((v * self.height as f64) as usize).min(self.height - 1);
+        (x, y)
+    }
+}
+
+impl Environment for Equirectangular {
+    fn radiance(&self, direction: &Vector) -> Color {
+        let (u, v) = self.position(direction);
+        let s = u * self.width as f64 - 0.5;
+        let t = v * self.height as f64 - 0.5;
+        let (x, y) = (s.floor(), t.floor());
+        let (fx, fy) = (s - x, t - y);
+        let (x, y) = (x as i64, y as i64);
+
+        let top = self.texel(x, y) * (1. - fx) + self.texel(x + 1, y) * fx;
+        let bottom = self.texel(x, y + 1) * (1. - fx) + self.texel(x + 1, y + 1) * fx;
+        (top * (1. - fy) + bottom * fy) * self.intensity
+    }
+
+    fn sample(&self, u: (f64, f64)) -> Option<Sample> {
+        let (v, row_pdf, row) = self.rows.sample(u.1);
+        let (u, column_pdf, _) = self.columns[row].sample(u.0);
+        let sin = (v * PI).sin();
+
+        if sin <= 0. || row_pdf * column_pdf == 0. {
+            return None;
+        }
+
+        let direction = self.direction(u, v);
+        let radiance = self.radiance(&direction);
+        let pdf = row_pdf * column_pdf / (2. * PI * PI * sin);
+        Some(Sample { direction, radiance, pdf })
+    }
+
+    fn pdf(&self, direction: &Vector) -> f64 {
+        let (u, v) = self.position(direction);
+        let sin = (v * PI).sin();
+
+        if sin <= 0. {
+            return 0.;
+        }
+
+        let (x, y) = self.cell(u, v);
+        self.rows.pdf(y) * self.columns[y].pdf(x) / (2. * PI * PI * sin)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sampling::{Rng,uniform_sphere};
+
+    // a dim sky with a small bright sun, turned away from the image seam
+    fn environment() -> Equirectangular {
+        let (width, height) = (32, 16);
+        let texels = (0..width * height)
+            .map(|index| match (index % width, index / width) {
+                (20, 4) | (21, 4) => Color::new(500., 450., 400.),
+                (x, y) => Color::new(0.2 + x as f64 * 0.01, 0.3, 0.1 + y as f64 * 0.05),
+            })
+            .collect();
+        Equirectangular::new(width, height, texels, 1., 0.7)
+    }
+
+    #[test]
+    fn pdf_integrates_to_one() {
+        let environment = environment();
+        let mut rng = Rng::new(5);
+        let count = 400_000;
+
+        let total: f64 = (0..count)
+            .map(|_| {
+                let (x, y, z) = uniform_sphere(rng.next_f64(), rng.next_f64());
+                environment.pdf(&Vector::new(x, y, z)) * 4. * PI
+            })
+            .sum();
+        let integral = total / count as f64;
+        assert!((integral - 1.).abs() < 0.02, "pdf integrates to {}", integral);
+    }
+
+    #[test]
+    fn pdf_matches_sample() {
+        let environment = environment();
+        let mut rng = Rng::new(9);
+        let count = 200_000;
+        let mut solid_angle = 0.;
+        let mut mismatched = 0;
+
+        for _ in 0..count {
+            let sample = environment.sample((rng.next_f64(), rng.next_f64())).unwrap();
+            let pdf = environment.pdf(&sample.direction);
+            if (pdf - sample.pdf).abs() > sample.pdf * 1e-6 {
+                mismatched += 1;
+            }
+            // every texel is lit, so the samples cover the whole sphere
+            solid_angle += 1. / sample.pdf;
+        }
+
+        // only samples on a texel edge may round into the neighbor
+        assert!(mismatched < count / 1000, "{} samples disagree with pdf", mismatched);
+        let solid_angle = solid_angle / count as f64;
+        assert!((solid_angle / (4. * PI) - 1.).abs() < 0.02, "samples cover {} sr", solid_angle);
+    }
+}
@@ -0,0 +1,150 @@
+mod equirectangular;
+mod sky;
+
+use std::f64::consts::PI;
+use std::path::PathBuf;
+use crate::color::{Color,WHITE};
+use crate::geometry::Vector;
+use crate::sampling::uniform_sphere;
+use crate::texture::read_pixels;
+
+pub use equirectangular::Equirectangular;
+pub use sky::{Sky,solar_position};
+
+const UNIFORM_PDF: f64 = 1. / (4. * PI);
+
+// radiance arriving from infinitely far away, +z up
+pub trait Environment: Send + Sync {
+    // `direction` points away from the scene and must be normalized
+    fn radiance(&self, direction: &Vector) -> Color;
+
+    // uniform over the sphere unless the environment knows better
+    fn sample(&self, u: (f64, f64)) -> Option<Sample> {
+        let (x, y, z) = uniform_sphere(u.0, u.1);
+        let direction = Vector::new(x, y, z);
+        let radiance = self.radiance(&direction);
+        Some(Sample { direction, radiance, pdf: UNIFORM_PDF })
+    }
+
+    // solid angle density of `sample` producing `direction`
+    fn pdf(&self, _direction: &Vector) -> f64 {
+        UNIFORM_PDF
+    }
+
+    // directional light that comes with the environment, kept out of
+    // `radiance` so it is only ever sampled explicitly
+    fn sun(&self) -> Option<&Sun> {
+        None
+    }
+}
+
+pub struct Sun {
+    // towards the sun
+    pub direction: Vector<'static>,
+    // scales like the point light power
+    pub color: Color,
+}
+
+pub struct Sample {
+    pub direction: Vector<'static>,
+    pub radiance: Color,
+    pub pdf: f64,
+}
+
+pub struct Constant {
+    pub color: Color,
+}
+
+impl Environment for Constant {
+    fn radiance(&self, _direction: &Vector) -> Color {
+        self.color
+    }
+}
+
+// blends linearly from the horizon up to the zenith and down to the ground
+pub struct Gradient {
+    pub zenith: Color,
+    pub horizon: Color,
+    pub ground: Color,
+}
+
+impl Environment for Gradient {
+    fn radiance(&self, direction: &Vector) -> Color {
+        let z = direction.z.clamp(-1., 1.);
+        if z >= 0. {
+            self.horizon * (1. - z) + self.zenith * z
+        } else {
+            self.horizon * (1. + z) + self.ground * -z
+        }
+    }
+}
+
+#[derive(Debug,Clone,PartialEq)]
+pub enum Source {
+    Constant(Color),
+    Gradient { zenith: Color, horizon: Color, ground: Color },
+    // equirectangular image, +z along the top row
+    Image(PathBuf),
+    // degrees, azimuth clockwise from +y towards +x
+    Sky { turbidity: f64, elevation: f64, azimuth: f64 },
+}
+
+#[derive(Debug,Clone,PartialEq)]
+pub struct Settings {
+    pub source: Source,
+    pub intensity: f64,
+    // degrees around +z
+    pub rotation: f64,
+    // seen by camera rays, otherwise misses stay transparent
+    pub visible: bool,
+}
+
+impl Default for Settings {
+    // the white backdrop the renderer always had, lighting but not shown
+    fn default() -> Self {
+        Settings {
+            source: Source::Constant(WHITE),
+            intensity: 1.,
+            rotation: 0.,
+            visible: false,
+        }
+    }
+}
+
+impl Settings {
+    pub fn build(&self) -> Result<Box<dyn Environment>, String> {
+        let intensity = self.intensity;
+
+        Ok(match &self.source {
+            Source::Constant(color) => Box::new(Constant { color: *color * intensity }),
+            Source::Gradient { zenith, horizon, ground } => Box::new(Gradient {
+                zenith: *zenith * intensity,
+                horizon: *horizon * intensity,
+                ground: *ground * intensity,
+            }),
+            Source::Image(path) => {
+                // 8-bit images are display encoded, HDR files are linear anyway
+                let (width, height, texels) = read_pixels(path, true)?;
+                Box::new(Equirectangular::new(
+                    width,
+                    height,
+                    texels,
+                    intensity,
+                    self.rotation.to_radians(),
+                ))
+            }
+            Source::Sky { turbidity, elevation, azimuth } => {
+                if *elevation <= 0. {
+                    eprintln!("warning: The sun is below the horizon");
+                }
+                Box::new(Sky::new(
+                    *turbidity,
+                    elevation.to_radians(),
+                    // counterclockwise like the image rotation
+                    (azimuth - self.rotation).to_radians(),
+                    intensity,
+                ))
+            }
+        })
+    }
+}
@@ -0,0 +1,183 @@
+use std::f64::consts::PI;
+use crate::color::Color;
+use crate::geometry::Vector;
+use super::{Environment,Sun};
+
+// maps kcd/m² to scene radiance so a white surface under the noon sun comes
+// out near 1
+const SKY_SCALE: f64 = 0.025;
+// klx of sunlight above the atmosphere
+const SOLAR_ILLUMINANCE: f64 = 127.5;
+// the sky below the horizon is mirrored and darkened by this much
+const GROUND_ALBEDO: f64 = 0.3;
+// wavelengths in μm standing in for the red, green and blue channels
+const WAVELENGTHS: [f64; 3] = [0.68, 0.55, 0.44];
+
+// Perez distribution coefficients A to E, linear in turbidity
+const PEREZ_Y: [[f64; 2]; 5] = [
+    [0.1787, -1.4630], [-0.3554, 0.4275], [-0.0227, 5.3251], [0.1206, -2.5771], [-0.0670, 0.3703],
+];
+const PEREZ_X: [[f64; 2]; 5] = [
+    [-0.0193, -0.2592], [-0.0665, 0.0008], [-0.0004, 0.2125], [-0.0641, -0.8989], [-0.0033, 0.0452],
+];
+const PEREZ_CHROMA_Y: [[f64; 2]; 5] = [
+    [-0.0167, -0.2608], [-0.0950, 0.0092], [-0.0079, 0.2102], [-0.0441, -1.6537], [-0.0109, 0.0529],
+];
+
+// zenith chromaticity, rows for T², T and 1, columns for θs³, θs², θs and 1
+const ZENITH_X: [[f64; 4]; 3] = [
+    [0.00166, -0.00375, 0.00209, 0.],
+    [-0.02903, 0.06377, -0.03202, 0.00394],
+    [0.11693, -0.21196, 0.06052, 0.25886],
+];
+const ZENITH_Y: [[f64; 4]; 3] = [
+    [0.00275, -0.00610, 0.00317, 0.],
+    [-0.04214, 0.08970, -0.04153, 0.00516],
+    [0.15346, -0.26756, 0.06670, 0.26688],
+];
+
+struct Perez {
+    coefficients: [f64; 5],
+    zenith: f64,
+}
+
+impl Perez {
+    fn new(table: &[[f64; 2]; 5], turbidity: f64, zenith: f64, sun_theta: f64) -> Self {
+        let coefficients = table.map(|[slope, intercept]| slope * turbidity + intercept);
+        let mut perez = Perez { coefficients, zenith: 1. };
+        // scaled so the distribution passes through the zenith value
+        perez.zenith = zenith / perez.distribution(0., sun_theta);
+        perez
+    }
+
+    fn distribution(&self, theta: f64, gamma: f64) -> f64 {
+        let [a, b, c, d, e] = self.coefficients;
+        (1. + a * (b / theta.cos()).exp()) * (1. + c * (d * gamma).exp() + e * gamma.cos().powi(2))
+    }
+
+    fn value(&self, theta: f64, gamma: f64) -> f64 {
+        self.zenith * self.distribution(theta, gamma)
+    }
+}
+
+fn zenith_chromaticity(table: &[[f64; 4]; 3], turbidity: f64, sun_theta: f64) -> f64 {
+    let t = [turbidity * turbidity, turbidity, 1.];
+    let s = [sun_theta.powi(3), sun_theta.powi(2), sun_theta, 1.];
+    (0..3)
+        .map(|row| (0..4).map(|column| t[row] * table[row][column] * s[column]).sum::<f64>())
+        .sum()
+}
+
+// CIE xyY to linear sRGB
+fn xyy_to_rgb(x: f64, y: f64, luminance: f64) -> Color {
+    let big_x = x / y * luminance;
+    let big_z = (1. - x - y) / y * luminance;
+    Color::new(
+        (3.2406 * big_x - 1.5372 * luminance - 0.4986 * big_z).max(0.),
+        (-0.9689 * big_x + 1.8758 * luminance + 0.0415 * big_z).max(0.),
+        (0.0557 * big_x - 0.2040 * luminance + 1.0570 * big_z).max(0.),
+    )
+}
+
+// Rayleigh and aerosol extinction of direct sunlight, Preetham et al. appendix
+fn sun_transmittance(turbidity: f64, sun_theta: f64) -> Color {
+    let degrees = sun_theta.to_degrees();
+    let air_mass = 1. / (sun_theta.cos() + 0.15 * (93.885 - degrees).powf(-1.253));
+    let beta = 0.04608365822050 * turbidity - 0.04586025928522;
+    let [r, g, b] = WAVELENGTHS.map(|lambda| {
+        let rayleigh = (-0.008735 * lambda.powf(-4.08) * air_mass).exp();
+        let aerosol = (-beta * lambda.powf(-1.3) * air_mass).exp();
+        rayleigh * aerosol
+    });
+    Color::new(r, g, b)
+}
+
+// Preetham, Shirley and Smits analytic daylight
+pub struct Sky {
+    sun_direction: Vector<'static>,
+    luminance: Perez,
+    x: Perez,
+    y: Perez,
+    intensity: f64,
+    sun: Option<Sun>,
+}
+
+impl Sky {
+    // angles in radians, azimuth clockwise from +y (north) towards +x (east)
+    pub fn new(turbidity: f64, elevation: f64, azimuth: f64, intensity: f64) -> Self {
+        // the model only covers suns above the horizon
+        let sun_theta = (PI / 2. - elevation).clamp(0., PI / 2.);
+        let sun_direction = Vector::new(
+            sun_theta.sin() * azimuth.sin(),
+            sun_theta.sin() * azimuth.cos(),
+            sun_theta.cos(),
+        );
+
+        let chi = (4. / 9. - turbidity / 120.) * (PI - 2. * sun_theta);
+        let zenith = (4.0453 * turbidity - 4.9710) * chi.tan() - 0.2155 * turbidity + 2.4192;
+        let zenith_x = zenith_chromaticity(&ZENITH_X, turbidity, sun_theta);
+        let zenith_y = zenith_chromaticity(&ZENITH_Y, turbidity, sun_theta);
+
+        let sun = (elevation > 0.).then(|| Sun {
+            direction: sun_direction.clone(),
+            color: sun_transmittance(turbidity, sun_theta)
+                * (SOLAR_ILLUMINANCE / PI * SKY_SCALE * intensity),
+        });
+
+        Sky {
+            luminance: Perez::new(&PEREZ_Y, turbidity, zenith.max(0.), sun_theta),
+            x: Perez::new(&PEREZ_X, turbidity, zenith_x, sun_theta),
+            y: Perez::new(&PEREZ_CHROMA_Y, turbidity, zenith_y, sun_theta),
+            sun_direction,
+            intensity,
+            sun,
+        }
+    }
+}
+
+impl Environment for Sky {
+    fn radiance(&self, direction: &Vector) -> Color {
+        let below = direction.z < 0.;
+        let up = Vector::new(direction.x, direction.y, direction.z.abs());
+
+        // keeps the Perez 1/cos term finite along the horizon
+        let theta = up.z.clamp(-1., 1.).acos().min(PI / 2. - 1e-3);
+        let gamma = up.dot_product(&self.sun_direction).clamp(-1., 1.).acos();
+
+        let color = xyy_to_rgb(
+            self.x.value(theta, gamma),
+            self.y.value(theta, gamma),
+            self.luminance.value(theta, gamma),
+        ) * (SKY_SCALE * self.intensity);
+
+        if below { color * GROUND_ALBEDO } else { color }
+    }
+
+    fn sun(&self) -> Option<&Sun> {
+        self.sun.as_ref()
+    }
+}
+
+// sun elevation and azimuth in radians for a day of the year, local solar
+// time in hours and a latitude in radians
+pub fn solar_position(day: u32, hours: f64, latitude: f64) -> (f64, f64) {
+    let declination = 23.44f64.to_radians() * (2. * PI * (284. + day as f64) / 365.).sin();
+    let hour_angle = (15. * (hours - 12.)).to_radians();
+
+    let sin_elevation = latitude.sin() * declination.sin()
+        + latitude.cos() * declination.cos() * hour_angle.cos();
+    let elevation = sin_elevation.clamp(-1., 1.).asin();
+
+    let denominator = elevation.cos() * latitude.cos();
+    let azimuth = if denominator.abs() < 1e-9 {
+        // at the zenith or a pole any azimuth will do
+        PI
+    } else {
+        let cos = (declination.sin() - sin_elevation * latitude.sin()) / denominator;
+        let azimuth = cos.clamp(-1., 1.).acos();
+        // afternoon suns sit west of the meridian
+        if hour_angle > 0. { 2. * PI - azimuth } else { azimuth }
+    };
+
+    (elevation, azimuth)
+}
@@ -0,0 +1,50 @@
+use crate::color::{Color,BLACK,WHITE};
+
+// Linear radiance premultiplied by coverage
+#[derive(Clone)]
+pub struct Framebuffer {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<Color>,
+    pub alpha: Vec<f64>,
+}
+
+impl Framebuffer {
+    pub fn new(width: u32, height: u32) -> Self {
+        let size = (width * height) as usize;
+        Framebuffer {
+            width,
+            height,
+            pixels: vec![BLACK; size],
+            alpha: vec![0.; size],
+        }
+    }
+
+    fn index(&self, x: u32, y: u32) -> usize {
+        (y * self.width + x) as usize
+    }
+
+    pub fn set_pixel(&mut self, x: u32, y: u32, color: Color, alpha: f64) {
+        let index = self.index(x, y);
+        self.pixels[index] = color;
+        self.alpha[index] = alpha;
+    }
+
+    pub fn pixel(&self, x: u32, y: u32) -> (Color, f64) {
+        let index = self.index(x, y);
+        (self.pixels[index], self.alpha[index])
+    }
+
+    pub fn flattened(&self, index: usize) -> Color {
+        self.pixels[index] + WHITE * (1. - self.alpha[index])
+    }
+
+    pub fn straight(&self, index: usize) -> Color {
+        let alpha = self.alpha[index];
+        if alpha > 0. {
+            self.pixels[index] * (1. / alpha)
+        } else {
+            BLACK
+        }
+    }
+}
@@ -1,4 +1,3 @@
-use std::f64::{EPSILON,INFINITY};
 use std::mem::swap;
 
 use super::Point;
@@ -17,8 +16,8 @@ pub fn intersection(vector: &Vector, face: &Trigon) -> f64 {
     let pvec = vector.cross_product(&edge2);
     let det = edge1.dot_product(&pvec);
 
-    if det < EPSILON && det > -EPSILON {
-        return INFINITY;
+    if det < f64::EPSILON && det > -f64::EPSILON {
+        return f64::INFINITY;
     }
 
     let inv_det = 1.0 / det;
@@ -26,18 +25,24 @@ pub fn intersection(vector: &Vector, face: &Trigon) -> f64 {
     let tvec = Vector::from(vector.origin - p0);
     let u = tvec.dot_product(&pvec) * inv_det;
 
-    if u < 0. || u > 1. {
-        return INFINITY;
+    if !(0. ..=1.).contains(&u) {
+        return f64::INFINITY;
     }
 
     let qvec = tvec.cross_product(&edge1);
     let v = vector.dot_product(&qvec) * inv_det;
 
     if v < 0. || u + v > 1. {
-        return INFINITY;
+        return f64::INFINITY;
     }
 
-    edge2.dot_product(&qvec) * inv_det
+    let t = edge2.dot_product(&qvec) * inv_det;
+
+    if t <= f64::EPSILON {
+        return f64::INFINITY;
+    }
+
+    t
 }
 
 pub fn vector_box_intersection(
@@ -52,12 +57,12 @@ pub fn vector_box_intersection(
     let (m, n, p) = (normalized.x, normalized.y, normalized.z);
     let (x, y, z) = (vector.origin.x, vector.origin.y, vector.origin.z);
 
-    let mut t_near = -INFINITY;
-    let mut t_far = INFINITY;
+    let mut t_near = f64::NEG_INFINITY;
+    let mut t_far = f64::INFINITY;
 
     if m == 0. {
         if x > x1 || x < x0 {
-            return INFINITY;
+            return f64::INFINITY;
         }
     } else {
         t_near = (x0 - x) / m;
@@ -67,7 +72,7 @@ pub fn vector_box_intersection(
 
     if n == 0. {
         if y > y1 || y < y0 {
-            return INFINITY;
+            return f64::INFINITY;
         }
     } else {
         let mut t1y = (y0 - y) / n;
@@ -80,12 +85,12 @@ pub fn vector_box_intersection(
     }
 
     if t_near > t_far || t_far < 0. {
-        return INFINITY;
+        return f64::INFINITY;
     }
 
     if p == 0. {
         if z > z1 || z < z0 {
-            return INFINITY;
+            return f64::INFINITY;
         }
     } else {
         let mut t1z = (z0 - z) / p;
@@ -98,7 +103,7 @@ pub fn vector_box_intersection(
     }
 
     if t_near > t_far || t_far == 0. {
-        return INFINITY;
+        return f64::INFINITY;
     }
 
     t_near
@@ -158,6 +163,7 @@ pub fn trigon_box_intersection(
     true
 }
 
+#[allow(clippy::too_many_arguments)]
 fn test_axis(
     axis: &Vector,
     v0: &Vector,
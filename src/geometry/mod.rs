@@ -1,4 +1,5 @@
 pub mod utils;
+pub mod optics;
 mod point;
 mod vector;
 mod trigon;
@@ -0,0 +1,44 @@
+use super::Vector;
+
+pub fn reflect<'b>(direction: &Vector, normal: &Vector) -> Vector<'b> {
+    let scale = 2. * direction.dot_product(normal);
+    Vector::new(
+        direction.x - normal.x * scale,
+        direction.y - normal.y * scale,
+        direction.z - normal.z * scale,
+    )
+}
+
+// `normal` faces the incoming direction, `eta` is n_incident / n_transmitted
+pub fn refract<'b>(direction: &Vector, normal: &Vector, eta: f64) -> Option<Vector<'b>> {
+    let cos_i = -direction.dot_product(normal);
+    let sin2_t = eta * eta * (1. - cos_i * cos_i);
+
+    if sin2_t > 1. {
+        return None;
+    }
+
+    let cos_t = (1. - sin2_t).sqrt();
+    let scale = eta * cos_i - cos_t;
+
+    Some(Vector::new(
+        direction.x * eta + normal.x * scale,
+        direction.y * eta + normal.y * scale,
+        direction.z * eta + normal.z * scale,
+    ))
+}
+
+pub fn fresnel_dielectric(cos_i: f64, n_i: f64, n_t: f64) -> f64 {
+    let cos_i = cos_i.clamp(0., 1.);
+    let sin_t = n_i / n_t * (1. - cos_i * cos_i).sqrt();
+
+    if sin_t >= 1. {
+        return 1.;
+    }
+
+    let cos_t = (1. - sin_t * sin_t).sqrt();
+    let rs = (n_i * cos_i - n_t * cos_t) / (n_i * cos_i + n_t * cos_t);
+    let rp = (n_t * cos_i - n_i * cos_t) / (n_t * cos_i + n_i * cos_t);
+
+    (rs * rs + rp * rp) / 2.
+}
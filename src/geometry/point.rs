@@ -26,7 +26,7 @@ impl Add for Point {
     }
 }
 
-impl<'a, 'b> Add<&'b Point> for &'a Point {
+impl<'b> Add<&'b Point> for &Point {
     type Output = Point;
 
     fn add(self, other: &'b Point) -> Point {
@@ -50,7 +50,7 @@ impl Sub for Point {
     }
 }
 
-impl<'a, 'b> Sub<&'b Point> for &'a Point {
+impl<'b> Sub<&'b Point> for &Point {
     type Output = Point;
 
     fn sub(self, other: &'b Point) -> Point {
@@ -74,7 +74,7 @@ impl Mul for Point {
     }
 }
 
-impl<'a, 'b> Mul<&'b Point> for &'a Point {
+impl<'b> Mul<&'b Point> for &Point {
     type Output = Point;
 
     fn mul(self, other: &'b Point) -> Point {
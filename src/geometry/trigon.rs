@@ -7,6 +7,14 @@ pub struct Trigon<'a> {
     pub points: Vec<Point>,
     pub normal: Vector<'a>,
     pub centroid: Point,
+    pub material: usize,
+    // index into the scene objects
+    pub object: usize,
+    // position in the scene faces, numbered by `Scene::new`
+    pub index: usize,
+    pub uvs: Option<[(f64, f64); 3]>,
+    // per-corner tangent with the bitangent sign in the last component
+    pub tangents: Option<[[f64; 4]; 3]>,
 }
 
 impl<'a> Trigon<'a> {
@@ -25,8 +33,110 @@ impl<'a> Trigon<'a> {
             points: vec![p1, p2, p3],
             normal,
             centroid,
+            material: 0,
+            object: 0,
+            index: 0,
+            uvs: None,
+            tangents: None,
         }
     }
+
+    // weights of the three corners at a point on the face
+    pub fn barycentric(&self, point: &Point) -> (f64, f64, f64) {
+        let edge1 = Vector::from(&self.points[1] - &self.points[0]);
+        let edge2 = Vector::from(&self.points[2] - &self.points[0]);
+        let offset = Vector::from(point - &self.points[0]);
+
+        let d11 = edge1.dot_product(&edge1);
+        let d12 = edge1.dot_product(&edge2);
+        let d22 = edge2.dot_product(&edge2);
+        let d1 = offset.dot_product(&edge1);
+        let d2 = offset.dot_product(&edge2);
+        let denominator = d11 * d22 - d12 * d12;
+
+        if denominator.abs() < f64::EPSILON {
+            return (1., 0., 0.);
+        }
+
+        let b1 = (d22 * d1 - d12 * d2) / denominator;
+        let b2 = (d11 * d2 - d12 * d1) / denominator;
+        (1. - b1 - b2, b1, b2)
+    }
+
+    pub fn texture_coordinates(&self, point: &Point) -> Option<(f64, f64)> {
+        let uvs = self.uvs?;
+        let (w0, w1, w2) = self.barycentric(point);
+        Some((
+            uvs[0].0 * w0 + uvs[1].0 * w1 + uvs[2].0 * w2,
+            uvs[0].1 * w0 + uvs[1].1 * w1 + uvs[2].1 * w2,
+        ))
+    }
+
+    // derivatives of position with respect to u and v
+    pub fn position_derivatives<'b>(&self) -> Option<(Vector<'b>, Vector<'b>)> {
+        let uvs = self.uvs?;
+        let edge1 = Vector::from(&self.points[1] - &self.points[0]);
+        let edge2 = Vector::from(&self.points[2] - &self.points[0]);
+        let (du1, dv1) = (uvs[1].0 - uvs[0].0, uvs[1].1 - uvs[0].1);
+        let (du2, dv2) = (uvs[2].0 - uvs[0].0, uvs[2].1 - uvs[0].1);
+
+        let determinant = du1 * dv2 - du2 * dv1;
+        if determinant.abs() < f64::EPSILON {
+            return None;
+        }
+
+        let r = 1. / determinant;
+        let combine = |a: f64, b: f64| Vector::new(
+            (edge1.x * a + edge2.x * b) * r,
+            (edge1.y * a + edge2.y * b) * r,
+            (edge1.z * a + edge2.z * b) * r,
+        );
+
+        Some((combine(dv2, -dv1), combine(-du2, du1)))
+    }
+
+    // interpolated vertex tangent and bitangent sign
+    pub fn tangent_at<'b>(&self, point: &Point) -> Option<(Vector<'b>, f64)> {
+        let tangents = self.tangents?;
+        let (w0, w1, w2) = self.barycentric(point);
+        let component = |axis: usize| {
+            tangents[0][axis] * w0 + tangents[1][axis] * w1 + tangents[2][axis] * w2
+        };
+        let sign = if component(3) < 0. { -1. } else { 1. };
+        Some((Vector::new(component(0), component(1), component(2)), sign))
+    }
+
+    // world space gradients of u and v within the plane of the face
+    pub fn uv_gradients<'b>(&self) -> Option<(Vector<'b>, Vector<'b>)> {
+        let uvs = self.uvs?;
+        let edge1 = Vector::from(&self.points[1] - &self.points[0]);
+        let edge2 = Vector::from(&self.points[2] - &self.points[0]);
+
+        let d11 = edge1.dot_product(&edge1);
+        let d12 = edge1.dot_product(&edge2);
+        let d22 = edge2.dot_product(&edge2);
+        let denominator = d11 * d22 - d12 * d12;
+
+        if denominator.abs() < f64::EPSILON {
+            return None;
+        }
+
+        // solve g . edge1 = delta1, g . edge2 = delta2 with g spanned by the edges
+        let gradient = |delta1: f64, delta2: f64| {
+            let a = (d22 * delta1 - d12 * delta2) / denominator;
+            let b = (d11 * delta2 - d12 * delta1) / denominator;
+            Vector::new(
+                edge1.x * a + edge2.x * b,
+                edge1.y * a + edge2.y * b,
+                edge1.z * a + edge2.z * b,
+            )
+        };
+
+        Some((
+            gradient(uvs[1].0 - uvs[0].0, uvs[2].0 - uvs[0].0),
+            gradient(uvs[1].1 - uvs[0].1, uvs[2].1 - uvs[0].1),
+        ))
+    }
 }
 
 impl<'a> PartialEq for Trigon<'a> {
@@ -12,14 +12,14 @@ pub fn trigon_brightness(
     light_pos: &Point,
     trigon: &Trigon,
     tree: &Octree,
-) -> u8 {
+) -> f64 {
     let mut light_vector = Vector::from(&trigon.centroid - light_pos);
-    light_vector.set_origin(&light_pos);
+    light_vector.set_origin(light_pos);
 
     let (distance, obstacle) = tree.intersection(&light_vector);
 
-    if distance < std::f64::INFINITY && obstacle.unwrap() != trigon {
-        return 0;
+    if distance < f64::INFINITY && obstacle.unwrap() != trigon {
+        return 0.;
     }
 
     let (a, b, c) = (
@@ -36,7 +36,5 @@ pub fn trigon_brightness(
     let len1 = trigon.normal.length;
     let len2 = light_vector.length;
 
-    let cos = (a * m + b * n + c * p).abs() / (len1 * len2);
-
-    (cos * 255.).floor() as u8
+    (a * m + b * n + c * p).abs() / (len1 * len2)
 }
\ No newline at end of file
@@ -14,7 +14,7 @@ pub struct Vector<'a> {
 impl<'a> Vector<'a> {
     pub fn new(x: f64, y: f64, z: f64) -> Self {
         let origin = &START;
-        let length = distance(&origin, &Point::new(x, y, z));
+        let length = distance(origin, &Point::new(x, y, z));
         Vector {
             x,
             y,
@@ -26,7 +26,7 @@ impl<'a> Vector<'a> {
 
     pub fn from(direction: Point) -> Self {
         let origin = &START;
-        let length = distance(&origin, &direction);
+        let length = distance(origin, &direction);
         Vector {
             x: direction.x,
             y: direction.y,
@@ -47,21 +47,21 @@ impl<'a> Vector<'a> {
         Vector::new(x, y, z)
     }
 
-    pub fn normalize(&self) -> Vector {
+    pub fn normalize<'b>(&self) -> Vector<'b> {
         let x = self.x / self.length;
         let y = self.y / self.length;
         let z = self.z / self.length;
         Vector::new(x, y, z)
     }
 
-    pub fn cross_product<'b>(&self, other: &'a Vector) -> Vector<'b> {
+    pub fn cross_product<'b>(&self, other: &Vector) -> Vector<'b> {
         let x = self.y * other.z - self.z * other.y;
         let y = self.z * other.x - self.x * other.z;
         let z = self.x * other.y - self.y * other.x;
         Vector::new(x, y, z)
     }
 
-    pub fn dot_product(&self, other: &'a Vector) -> f64 {
+    pub fn dot_product(&self, other: &Vector) -> f64 {
         let x = self.x * other.x;
         let y = self.y * other.y;
         let z = self.z * other.z;
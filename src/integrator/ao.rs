@@ -0,0 +1,31 @@
+use crate::color::Color;
+use crate::geometry::{Point,Vector};
+use crate::sampling::{Rng,cosine_hemisphere,to_world};
+use super::{Context,Integrator};
+
+pub struct AmbientOcclusion<'s> {
+    pub context: &'s Context<'s, 's>,
+    pub samples: u32,
+    pub distance: f64,
+}
+
+impl<'s> Integrator for AmbientOcclusion<'s> {
+    fn radiance(&self, origin: &Point, direction: &Vector, rng: &mut Rng) -> Option<Color> {
+        let direction = direction.normalize();
+        let hit = match self.context.intersect(origin, &direction) {
+            Some(hit) => hit,
+            None => return self.context.background(&direction),
+        };
+        let outside = hit.outside();
+
+        let unoccluded = (0..self.samples)
+            .filter(|_| {
+                let local = cosine_hemisphere(rng.next_f64(), rng.next_f64());
+                let ray = to_world(local, &hit.normal);
+                !self.context.occluded(&outside, &ray, self.distance)
+            })
+            .count();
+
+        Some(Color::gray(unoccluded as f64 / self.samples as f64))
+    }
+}
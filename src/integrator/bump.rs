@@ -0,0 +1,100 @@
+use crate::geometry::Vector;
+use crate::material::Material;
+use crate::sampling::basis;
+use crate::texture::{Lookup,Texture};
+use super::{Hit,along};
+
+// finite difference step in texture space when the footprint is unknown
+const DEFAULT_STEP: f64 = 5e-4;
+
+// the geometric normal perturbed by the material's normal or bump map, on
+// the same side as `hit.normal`; `None` when the material has neither
+pub fn shading_normal(hit: &Hit, material: &Material, lookup: &Lookup) -> Option<Vector<'static>> {
+    let outward: Vector = hit.trigon.normal.normalize();
+
+    let perturbed = match (&material.normal_map, &material.bump_map) {
+        (Some(map), _) => normal_map(hit, &outward, map.as_ref(), lookup),
+        (None, Some(map)) => bump_map(hit, &outward, map.as_ref(), material.bump_scale, lookup),
+        (None, None) => None,
+    };
+
+    perturbed.map(|normal| if hit.entering { normal } else { normal.multiply(-1.) })
+}
+
+// tangent space normal map with the MikkTSpace frame built by the loader
+fn normal_map(
+    hit: &Hit,
+    normal: &Vector,
+    map: &dyn Texture,
+    lookup: &Lookup,
+) -> Option<Vector<'static>> {
+    let texel = map.color(lookup)?;
+    let (tangent, sign) = hit.trigon.tangent_at(&hit.point)?;
+
+    let tangent = Vector::new(
+        tangent.x - normal.x * normal.dot_product(&tangent),
+        tangent.y - normal.y * normal.dot_product(&tangent),
+        tangent.z - normal.z * normal.dot_product(&tangent),
+    );
+    if tangent.length < f64::EPSILON {
+        return None;
+    }
+    let tangent: Vector = tangent.normalize();
+    let bitangent = normal.cross_product(&tangent).multiply(sign);
+
+    let (x, y, z) = (2. * texel.r - 1., 2. * texel.g - 1., 2. * texel.b - 1.);
+    let perturbed = Vector::new(
+        tangent.x * x + bitangent.x * y + normal.x * z,
+        tangent.y * x + bitangent.y * y + normal.y * z,
+        tangent.z * x + bitangent.z * y + normal.z * z,
+    );
+
+    (perturbed.length > f64::EPSILON).then(|| perturbed.normalize())
+}
+
+// grayscale height map: displaces the surface along the normal and takes
+// the normal of the displaced surface from finite differences
+fn bump_map(
+    hit: &Hit,
+    normal: &Vector,
+    map: &dyn Texture,
+    scale: f64,
+    lookup: &Lookup,
+) -> Option<Vector<'static>> {
+    // faces without texture coordinates still carry object space patterns
+    let (dpdu, dpdv) = hit.trigon.position_derivatives().unwrap_or_else(|| basis(normal));
+
+    let step = |axis: fn(&(f64, f64)) -> f64| {
+        let width = 0.5 * (axis(&lookup.footprint[0]).abs() + axis(&lookup.footprint[1]).abs());
+        if width > 0. { width } else { DEFAULT_STEP }
+    };
+    let du = step(|(u, _)| *u);
+    let dv = step(|(_, v)| *v);
+
+    let shifted = |direction: &Vector, distance: f64, offset: (f64, f64)| Lookup {
+        point: along(&lookup.point, direction, distance),
+        uv: lookup.uv.map(|(u, v)| (u + offset.0, v + offset.1)),
+        ..lookup.clone()
+    };
+
+    let height = map.value(lookup)?;
+    let height_u = map.value(&shifted(&dpdu, du, (du, 0.)))?;
+    let height_v = map.value(&shifted(&dpdv, dv, (0., dv)))?;
+
+    let slope_u = (height_u - height) / du * scale;
+    let slope_v = (height_v - height) / dv * scale;
+
+    let tilt = |derivative: &Vector, slope: f64| Vector::new(
+        derivative.x + normal.x * slope,
+        derivative.y + normal.y * slope,
+        derivative.z + normal.z * slope,
+    );
+    let perturbed = tilt(&dpdu, slope_u).cross_product(&tilt(&dpdv, slope_v));
+
+    if perturbed.length < f64::EPSILON {
+        return None;
+    }
+
+    let perturbed: Vector = perturbed.normalize();
+    Some(if perturbed.dot_product(normal) < 0. { perturbed.multiply(-1.) } else { perturbed })
+}
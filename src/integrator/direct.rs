@@ -0,0 +1,28 @@
+use crate::color::Color;
+use crate::geometry::{Point,Vector};
+use crate::sampling::Rng;
+use super::{Context,Integrator,shading_normal};
+
+pub struct Direct<'s> {
+    pub context: &'s Context<'s, 's>,
+}
+
+impl<'s> Integrator for Direct<'s> {
+    fn radiance(&self, origin: &Point, direction: &Vector, rng: &mut Rng) -> Option<Color> {
+        let context = self.context;
+        let direction = direction.normalize();
+        let hit = match context.intersect(origin, &direction) {
+            Some(hit) => hit,
+            None => return context.background(&direction),
+        };
+        let lookup = context.lookup(&hit, &direction, hit.distance);
+        let material = context.scene.material(hit.trigon).textured(&lookup);
+        let shading = shading_normal(&hit, &material, &lookup);
+        let brightness = context.brightness(&hit, shading.as_ref());
+        let sunlight = context.sunlight(&hit, shading.as_ref());
+        let area = context.area_light(&hit, shading.as_ref(), rng);
+        let direct = material.diffuse * (brightness * context.light_power)
+            + material.diffuse * (sunlight + area);
+        Some(direct + material.emission)
+    }
+}
@@ -0,0 +1,24 @@
+use crate::color::Color;
+use crate::geometry::{Point,Vector};
+use crate::sampling::Rng;
+use super::{Context,Integrator,shading_normal};
+
+// unlit preview: material color scaled by how directly the surface faces the camera
+pub struct Flat<'s> {
+    pub context: &'s Context<'s, 's>,
+}
+
+impl<'s> Integrator for Flat<'s> {
+    fn radiance(&self, origin: &Point, direction: &Vector, _rng: &mut Rng) -> Option<Color> {
+        let direction = direction.normalize();
+        let hit = match self.context.intersect(origin, &direction) {
+            Some(hit) => hit,
+            None => return self.context.background(&direction),
+        };
+        let lookup = self.context.lookup(&hit, &direction, hit.distance);
+        let material = self.context.scene.material(hit.trigon).textured(&lookup);
+        let shading = shading_normal(&hit, &material, &lookup);
+        let facing = -direction.dot_product(shading.as_ref().unwrap_or(&hit.normal));
+        Some(material.diffuse * facing.max(0.))
+    }
+}
@@ -0,0 +1,279 @@
+mod flat;
+mod direct;
+mod whitted;
+mod path;
+mod ao;
+mod bump;
+
+use std::f64::consts::PI;
+use crate::color::{Color,BLACK};
+use crate::environment::Environment;
+use crate::lights::Lights;
+use crate::geometry::{Point,Vector,Trigon};
+use crate::geometry::utils::trigon_brightness;
+use crate::sampling::{Rng,basis};
+use crate::scene::Scene;
+use crate::texture::{Filter,Lookup};
+use crate::tree::Octree;
+
+use flat::Flat;
+use direct::Direct;
+use whitted::Whitted;
+use path::PathTracer;
+use ao::AmbientOcclusion;
+pub use bump::shading_normal;
+
+const SURFACE_OFFSET: f64 = 1e-6;
+// shadow rays to emitters stop this fraction short of the emitting face
+const SHADOW_EPSILON: f64 = 1e-4;
+// keeps the footprint of grazing hits finite
+const MIN_FOOTPRINT_COS: f64 = 0.05;
+
+pub trait Integrator: Sync {
+    // `None` when the camera ray leaves the scene and the environment is hidden
+    fn radiance(&self, origin: &Point, direction: &Vector, rng: &mut Rng) -> Option<Color>;
+}
+
+#[derive(Debug,Clone,PartialEq)]
+pub enum Settings {
+    Flat,
+    Direct,
+    Whitted { max_depth: u32 },
+    Path { max_depth: u32, roulette_depth: u32 },
+    AmbientOcclusion { samples: u32, distance: f64 },
+}
+
+impl Settings {
+    pub fn build<'s>(&self, context: &'s Context<'s, '_>) -> Box<dyn Integrator + 's> {
+        match *self {
+            Settings::Flat => Box::new(Flat { context }),
+            Settings::Direct => Box::new(Direct { context }),
+            Settings::Whitted { max_depth } => Box::new(Whitted { context, max_depth }),
+            Settings::Path { max_depth, roulette_depth } => {
+                // textured materials get their BSDF built per hit
+                let bsdfs = context
+                    .scene
+                    .materials
+                    .iter()
+                    .map(|material| (!material.is_textured()).then(|| material.bsdf()))
+                    .collect();
+                Box::new(PathTracer { context, bsdfs, max_depth, roulette_depth })
+            }
+            Settings::AmbientOcclusion { samples, distance } => {
+                Box::new(AmbientOcclusion { context, samples, distance })
+            }
+        }
+    }
+}
+
+pub struct Context<'s, 'a> {
+    pub scene: &'s Scene<'a>,
+    pub tree: &'s Octree<'a>,
+    pub light: &'s Point,
+    pub light_power: f64,
+    // angle subtended by one pixel, widening ray footprints with distance
+    pub pixel_spread: f64,
+    pub texture_filter: Filter,
+    pub environment: &'s dyn Environment,
+    pub environment_visible: bool,
+    pub lights: &'s Lights,
+}
+
+// light from one emitter sample: unit direction, radiance and solid angle pdf
+pub struct Emitted {
+    pub direction: Vector<'static>,
+    pub radiance: Color,
+    pub pdf: f64,
+}
+
+pub struct Hit<'a> {
+    pub trigon: &'a Trigon<'a>,
+    pub point: Point,
+    // faces against the incoming ray
+    pub normal: Vector<'static>,
+    pub entering: bool,
+    pub distance: f64,
+}
+
+impl<'a> Hit<'a> {
+    pub fn outside(&self) -> Point {
+        along(&self.point, &self.normal, SURFACE_OFFSET)
+    }
+
+    pub fn inside(&self) -> Point {
+        along(&self.point, &self.normal, -SURFACE_OFFSET)
+    }
+
+    // offset origin on the side of the surface `direction` leaves through
+    pub fn leaving(&self, direction: &Vector) -> Point {
+        if direction.dot_product(&self.normal) > 0. { self.outside() } else { self.inside() }
+    }
+}
+
+pub fn along(point: &Point, direction: &Vector, distance: f64) -> Point {
+    Point::new(
+        point.x + direction.x * distance,
+        point.y + direction.y * distance,
+        point.z + direction.z * distance,
+    )
+}
+
+impl<'s, 'a> Context<'s, 'a> {
+    // `direction` must be normalized
+    pub fn intersect(&self, origin: &Point, direction: &Vector) -> Option<Hit<'a>> {
+        let mut ray = direction.clone();
+        ray.set_origin(origin);
+
+        let (distance, trigon) = self.tree.intersection(&ray);
+        let trigon = trigon.filter(|_| distance < f64::INFINITY)?;
+
+        let mut normal = trigon.normal.normalize();
+        let entering = direction.dot_product(&normal) < 0.;
+        if !entering {
+            normal = normal.multiply(-1.);
+        }
+
+        Some(Hit {
+            trigon,
+            point: along(origin, direction, distance),
+            normal,
+            entering,
+            distance,
+        })
+    }
+
+    // texture lookup at `hit` for a ray cone that has travelled `travelled`
+    // from the camera; its footprint is stretched along the ray at grazing angles
+    pub fn lookup(&self, hit: &Hit, direction: &Vector, travelled: f64) -> Lookup {
+        let uv = hit.trigon.texture_coordinates(&hit.point);
+        let footprint = match hit.trigon.uv_gradients() {
+            Some((gradient_u, gradient_v)) => {
+                let normal = &hit.normal;
+                let cos = direction.dot_product(normal);
+                let along = Vector::new(
+                    direction.x - normal.x * cos,
+                    direction.y - normal.y * cos,
+                    direction.z - normal.z * cos,
+                );
+                let along: Vector = if along.length > f64::EPSILON {
+                    along.normalize()
+                } else {
+                    basis(normal).0
+                };
+                let across = normal.cross_product(&along);
+
+                let width = self.pixel_spread * travelled;
+                let major = along.multiply(width / cos.abs().max(MIN_FOOTPRINT_COS));
+                let minor = across.multiply(width);
+                let to_uv = |axis: &Vector| (gradient_u.dot_product(axis), gradient_v.dot_product(axis));
+                [to_uv(&major), to_uv(&minor)]
+            }
+            None => [(0., 0.); 2],
+        };
+
+        Lookup {
+            point: hit.point.clone(),
+            uv,
+            footprint,
+            filter: self.texture_filter,
+        }
+    }
+
+    // point light falloff used by the direct and Whitted shaders: flat per
+    // face, unless a normal or bump map tilts the shading normal
+    pub fn brightness(&self, hit: &Hit, shading_normal: Option<&Vector>) -> f64 {
+        let brightness = trigon_brightness(self.light, hit.trigon, self.tree);
+
+        match shading_normal {
+            Some(normal) if brightness > 0. => {
+                let to_light: Vector = Vector::from(self.light - &hit.point).normalize();
+                to_light.dot_product(normal).abs()
+            }
+            _ => brightness,
+        }
+    }
+
+    // sunlight for the direct and Whitted shaders, two-sided like the point
+    // light and scaled the same way
+    pub fn sunlight(&self, hit: &Hit, shading_normal: Option<&Vector>) -> Color {
+        let sun = match self.environment.sun() {
+            Some(sun) => sun,
+            None => return BLACK,
+        };
+
+        let facing = sun.direction.dot_product(&hit.normal) > 0.;
+        let origin = if facing { hit.outside() } else { hit.inside() };
+        if self.occluded(&origin, &sun.direction, f64::INFINITY) {
+            return BLACK;
+        }
+
+        let normal = shading_normal.unwrap_or(&hit.normal);
+        sun.color * sun.direction.dot_product(normal).abs()
+    }
+
+    // radiance leaving an emissive face, with any emission map applied
+    pub fn emitted(&self, trigon: &Trigon, point: &Point) -> Color {
+        let material = self.scene.material(trigon);
+        if material.emission_map.is_none() {
+            return material.emission;
+        }
+
+        let lookup = Lookup {
+            point: point.clone(),
+            uv: trigon.texture_coordinates(point),
+            footprint: [(0., 0.); 2],
+            filter: self.texture_filter,
+        };
+        material.textured(&lookup).emission
+    }
+
+    // one unoccluded sample of the emissive faces as seen from `hit`
+    pub fn sample_emitter(&self, hit: &Hit, rng: &mut Rng) -> Option<Emitted> {
+        if self.lights.lights.is_empty() {
+            return None;
+        }
+
+        let u = (rng.next_f64(), rng.next_f64(), rng.next_f64());
+        let sample = self.lights.sample(self.scene, &hit.point, u)?;
+        let trigon = &self.scene.faces[sample.face];
+
+        let radiance = self.emitted(trigon, &sample.point);
+        if radiance == BLACK {
+            return None;
+        }
+
+        let direction: Vector = Vector::from(&sample.point - &hit.point).normalize();
+        let origin = hit.leaving(&direction);
+        let to_light = Vector::from(&sample.point - &origin);
+        if self.occluded(&origin, &to_light, 1. - SHADOW_EPSILON) {
+            return None;
+        }
+
+        Some(Emitted { direction, radiance, pdf: sample.pdf })
+    }
+
+    // emitted light reflected by a white Lambertian surface at `hit` for the
+    // direct and Whitted shaders, two-sided like the point light
+    pub fn area_light(&self, hit: &Hit, shading_normal: Option<&Vector>, rng: &mut Rng) -> Color {
+        let emitted = match self.sample_emitter(hit, rng) {
+            Some(emitted) => emitted,
+            None => return BLACK,
+        };
+
+        let normal = shading_normal.unwrap_or(&hit.normal);
+        let cos = emitted.direction.dot_product(normal).abs();
+        emitted.radiance * (cos / (PI * emitted.pdf))
+    }
+
+    // what a camera ray that misses everything sees
+    pub fn background(&self, direction: &Vector) -> Option<Color> {
+        self.environment_visible.then(|| self.environment.radiance(direction))
+    }
+
+    // true when something lies closer than `distance` along `direction`
+    pub fn occluded(&self, origin: &Point, direction: &Vector, distance: f64) -> bool {
+        let mut ray = direction.clone();
+        ray.set_origin(origin);
+        self.tree.occluded(&ray, distance)
+    }
+}
@@ -0,0 +1,197 @@
+use std::f64::consts::PI;
+use crate::bsdf::{Bsdf,Direction};
+use crate::color::{Color,BLACK,WHITE};
+use crate::geometry::{Point,Vector};
+use crate::sampling::{Rng,Frame,power_heuristic};
+use super::{Context,Hit,Integrator,shading_normal};
+
+pub struct PathTracer<'s> {
+    pub context: &'s Context<'s, 's>,
+    pub bsdfs: Vec<Option<Box<dyn Bsdf>>>,
+    pub max_depth: u32,
+    pub roulette_depth: u32,
+}
+
+impl<'s> PathTracer<'s> {
+    // point light intensity is scaled by PI so a white Lambertian surface
+    // facing it reflects `light_power`, matching the direct shader
+    fn point_light(&self, hit: &Hit, frame: &Frame, bsdf: &dyn Bsdf, wo: &Direction) -> Color {
+        let to_light = Vector::from(self.context.light - &hit.point);
+        let direction: Vector = to_light.normalize();
+        let wi = frame.to_local(&direction);
+        let value = bsdf.evaluate(wo, &wi);
+
+        if value == BLACK {
+            return BLACK;
+        }
+
+        let origin = hit.leaving(&direction);
+        let to_light = Vector::from(self.context.light - &origin);
+        if self.context.occluded(&origin, &to_light, 1.) {
+            return BLACK;
+        }
+
+        value * (wi.z.abs() * PI * self.context.light_power)
+    }
+
+    fn sun_light(&self, hit: &Hit, frame: &Frame, bsdf: &dyn Bsdf, wo: &Direction) -> Color {
+        let sun = match self.context.environment.sun() {
+            Some(sun) => sun,
+            None => return BLACK,
+        };
+        let wi = frame.to_local(&sun.direction);
+        let value = bsdf.evaluate(wo, &wi);
+
+        if value == BLACK {
+            return BLACK;
+        }
+
+        let origin = hit.leaving(&sun.direction);
+        if self.context.occluded(&origin, &sun.direction, f64::INFINITY) {
+            return BLACK;
+        }
+
+        value * sun.color * (wi.z.abs() * PI)
+    }
+
+    fn area_light(
+        &self,
+        hit: &Hit,
+        frame: &Frame,
+        bsdf: &dyn Bsdf,
+        wo: &Direction,
+        rng: &mut Rng,
+    ) -> Color {
+        let emitted = match self.context.sample_emitter(hit, rng) {
+            Some(emitted) => emitted,
+            None => return BLACK,
+        };
+        let wi = frame.to_local(&emitted.direction);
+        let value = bsdf.evaluate(wo, &wi);
+
+        if value == BLACK {
+            return BLACK;
+        }
+
+        let weight = power_heuristic(emitted.pdf, bsdf.pdf(wo, &wi));
+        emitted.radiance * value * (wi.z.abs() / emitted.pdf * weight)
+    }
+
+    fn environment_light(
+        &self,
+        hit: &Hit,
+        frame: &Frame,
+        bsdf: &dyn Bsdf,
+        wo: &Direction,
+        rng: &mut Rng,
+    ) -> Color {
+        let sample = match self.context.environment.sample((rng.next_f64(), rng.next_f64())) {
+            Some(sample) if sample.pdf > 0. && sample.radiance != BLACK => sample,
+            _ => return BLACK,
+        };
+        let wi = frame.to_local(&sample.direction);
+        let value = bsdf.evaluate(wo, &wi);
+
+        if value == BLACK {
+            return BLACK;
+        }
+
+        let direction = &sample.direction;
+        if self.context.occluded(&hit.leaving(direction), direction, f64::INFINITY) {
+            return BLACK;
+        }
+
+        let weight = power_heuristic(sample.pdf, bsdf.pdf(wo, &wi));
+        sample.radiance * value * (wi.z.abs() / sample.pdf * weight)
+    }
+}
+
+impl<'s> Integrator for PathTracer<'s> {
+    fn radiance(&self, origin: &Point, direction: &Vector, rng: &mut Rng) -> Option<Color> {
+        let mut color = BLACK;
+        let mut throughput = WHITE;
+        let mut origin = origin.clone();
+        let mut direction: Vector = direction.normalize();
+        let mut bsdf_pdf = 0.;
+        let mut specular = true;
+        let mut travelled = 0.;
+
+        for depth in 0..=self.max_depth {
+            let hit = match self.context.intersect(&origin, &direction) {
+                Some(hit) => hit,
+                None => {
+                    if depth == 0 {
+                        return self.context.background(&direction);
+                    }
+                    let environment = self.context.environment;
+                    let weight = if specular {
+                        1.
+                    } else {
+                        power_heuristic(bsdf_pdf, environment.pdf(&direction))
+                    };
+                    color += throughput * environment.radiance(&direction) * weight;
+                    break;
+                }
+            };
+
+            travelled += hit.distance;
+            let lookup = self.context.lookup(&hit, &direction, travelled);
+            let material = self.context.scene.material(hit.trigon).textured(&lookup);
+            if material.emission != BLACK {
+                // emitters reached by BSDF sampling share the light with `area_light`
+                let weight = if specular {
+                    1.
+                } else {
+                    let light_pdf = self.context.lights.pdf(self.context.scene, &origin, hit.trigon.index, &hit.point);
+                    power_heuristic(bsdf_pdf, light_pdf)
+                };
+                color += throughput * material.emission * weight;
+            }
+
+            let textured;
+            let bsdf = match &self.bsdfs[hit.trigon.material] {
+                Some(bsdf) => bsdf.as_ref(),
+                None => {
+                    textured = material.bsdf();
+                    textured.as_ref()
+                }
+            };
+
+            // shading frame around the outward, possibly perturbed, normal
+            let normal = shading_normal(&hit, &material, &lookup).unwrap_or_else(|| hit.normal.clone());
+            let outward = if hit.entering { normal } else { normal.multiply(-1.) };
+            let frame = Frame::new(&outward);
+            let wo = frame.to_local(&direction.multiply(-1.));
+
+            if !bsdf.is_specular() {
+                let direct = self.point_light(&hit, &frame, bsdf, &wo)
+                    + self.sun_light(&hit, &frame, bsdf, &wo);
+                let area = self.area_light(&hit, &frame, bsdf, &wo, rng);
+                let environment = self.environment_light(&hit, &frame, bsdf, &wo, rng);
+                color += throughput * (direct + area + environment);
+            }
+
+            let u = (rng.next_f64(), rng.next_f64());
+            let sample = match bsdf.sample(&wo, u, rng.next_f64()) {
+                Some(sample) if sample.pdf > 0. => sample,
+                _ => break,
+            };
+
+            throughput = throughput * sample.value * (sample.wi.z.abs() / sample.pdf);
+            direction = frame.to_world(&sample.wi).normalize();
+            origin = hit.leaving(&direction);
+            bsdf_pdf = sample.pdf;
+            specular = sample.specular;
+
+            if depth >= self.roulette_depth {
+                let survival = throughput.r.max(throughput.g).max(throughput.b).min(0.95);
+                if rng.next_f64() >= survival {
+                    break;
+                }
+                throughput = throughput * (1. / survival);
+            }
+        }
+
+        Some(color)
+    }
+}
@@ -0,0 +1,94 @@
+use crate::color::{Color,BLACK};
+use crate::geometry::{Point,Vector};
+use crate::geometry::optics::{reflect,refract,fresnel_dielectric};
+use crate::sampling::Rng;
+use super::{Context,Hit,Integrator,shading_normal};
+
+pub struct Whitted<'s> {
+    pub context: &'s Context<'s, 's>,
+    pub max_depth: u32,
+}
+
+impl<'s> Whitted<'s> {
+    // `travelled` is the ray length from the camera up to `origin`
+    fn trace(
+        &self,
+        origin: &Point,
+        direction: &Vector,
+        depth: u32,
+        travelled: f64,
+        rng: &mut Rng,
+    ) -> Option<Color> {
+        let direction = direction.normalize();
+        let hit = self.context.intersect(origin, &direction)?;
+        Some(self.shade(&hit, &direction, depth, travelled + hit.distance, rng))
+    }
+
+    fn secondary(
+        &self,
+        origin: &Point,
+        direction: &Vector,
+        depth: u32,
+        travelled: f64,
+        rng: &mut Rng,
+    ) -> Color {
+        let direction = direction.normalize();
+        self.trace(origin, &direction, depth, travelled, rng)
+            .unwrap_or_else(|| self.context.environment.radiance(&direction))
+    }
+
+    fn shade(&self, hit: &Hit, direction: &Vector, depth: u32, travelled: f64, rng: &mut Rng) -> Color {
+        let context = self.context;
+        let lookup = context.lookup(hit, direction, travelled);
+        let material = context.scene.material(hit.trigon).textured(&lookup);
+        let shading = shading_normal(hit, &material, &lookup);
+        let brightness = context.brightness(hit, shading.as_ref());
+        let sunlight = context.sunlight(hit, shading.as_ref());
+        let area = context.area_light(hit, shading.as_ref(), rng);
+        let direct = material.diffuse * (brightness * context.light_power)
+            + material.diffuse * (sunlight + area)
+            + material.emission;
+        let opacity = 1. - material.transparency;
+
+        if depth >= self.max_depth {
+            return direct * opacity;
+        }
+
+        let normal = shading.as_ref().unwrap_or(&hit.normal);
+        let has_reflection = material.reflection != BLACK;
+        let has_transmission = material.transparency > 0.;
+
+        let reflected = if has_reflection || has_transmission {
+            let reflection = reflect(direction, normal);
+            self.secondary(&hit.outside(), &reflection, depth + 1, travelled, rng)
+        } else {
+            BLACK
+        };
+
+        let mut color = (direct + material.reflection * reflected) * opacity;
+
+        if has_transmission {
+            let (n_i, n_t) = if hit.entering { (1., material.ior) } else { (material.ior, 1.) };
+            let cos_i = -direction.dot_product(normal);
+            let fresnel = fresnel_dielectric(cos_i, n_i, n_t);
+
+            let transmitted = match refract(direction, normal, n_i / n_t) {
+                Some(refraction) => self.secondary(&hit.inside(), &refraction, depth + 1, travelled, rng),
+                None => BLACK,
+            };
+
+            color += (reflected * fresnel + material.transmission * transmitted * (1. - fresnel))
+                * material.transparency;
+        }
+
+        color
+    }
+}
+
+impl<'s> Integrator for Whitted<'s> {
+    fn radiance(&self, origin: &Point, direction: &Vector, rng: &mut Rng) -> Option<Color> {
+        let direction = direction.normalize();
+        self.trace(origin, &direction, 0, 0., rng)
+            .or_else(|| self.context.background(&direction))
+    }
+}
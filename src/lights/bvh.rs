@@ -0,0 +1,159 @@
+use crate::geometry::Point;
+use crate::tree::BoundingBox;
+
+// a light as the tree sees it: where it is and how much it emits
+pub struct Emitter {
+    pub min: Point,
+    pub max: Point,
+    pub power: f64,
+}
+
+enum Kind {
+    Leaf(usize),
+    Interior(usize, usize),
+}
+
+struct Node {
+    bounds: BoundingBox,
+    power: f64,
+    kind: Kind,
+}
+
+// binary hierarchy over the lights; a sample walks from the root choosing
+// each child by its estimated contribution, so the cost grows with depth
+// rather than with the number of lights
+pub struct LightTree {
+    nodes: Vec<Node>,
+    // branch taken at each level on the way to every light, root first
+    trails: Vec<(u64, u32)>,
+}
+
+fn union(emitters: &[Emitter], indices: &[usize]) -> (Point, Point) {
+    let mut min = Point::new(f64::INFINITY, f64::INFINITY, f64::INFINITY);
+    let mut max = Point::new(f64::NEG_INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY);
+
+    for emitter in indices.iter().map(|index| &emitters[*index]) {
+        min = Point::new(min.x.min(emitter.min.x), min.y.min(emitter.min.y), min.z.min(emitter.min.z));
+        max = Point::new(max.x.max(emitter.max.x), max.y.max(emitter.max.y), max.z.max(emitter.max.z));
+    }
+
+    (min, max)
+}
+
+fn centroid(emitter: &Emitter, axis: usize) -> f64 {
+    let (min, max) = (&emitter.min, &emitter.max);
+    [min.x + max.x, min.y + max.y, min.z + max.z][axis]
+}
+
+impl LightTree {
+    pub fn new(emitters: &[Emitter]) -> Self {
+        let mut tree = LightTree { nodes: vec![], trails: vec![(0, 0); emitters.len()] };
+
+        if !emitters.is_empty() {
+            let mut indices: Vec<usize> = (0..emitters.len()).collect();
+            tree.build(emitters, &mut indices, 0, 0);
+        }
+
+        tree
+    }
+
+    // splits at the centroid median along the widest axis
+    fn build(&mut self, emitters: &[Emitter], indices: &mut [usize], trail: u64, depth: u32) -> usize {
+        let (min, max) = union(emitters, indices);
+        let power = indices.iter().map(|index| emitters[*index].power).sum();
+        let bounds = BoundingBox::new(min, max);
+        let node = self.nodes.len();
+
+        // median splits keep the depth, and so the trail, within 64 levels
+        if indices.len() == 1 {
+            self.nodes.push(Node { bounds, power, kind: Kind::Leaf(indices[0]) });
+            self.trails[indices[0]] = (trail, depth);
+            return node;
+        }
+
+        let extents = &bounds.extents;
+        let axis = if extents.x >= extents.y && extents.x >= extents.z {
+            0
+        } else if extents.y >= extents.z {
+            1
+        } else {
+            2
+        };
+        let middle = indices.len() / 2;
+        indices.select_nth_unstable_by(middle, |a, b| {
+            centroid(&emitters[*a], axis).total_cmp(&centroid(&emitters[*b], axis))
+        });
+
+        self.nodes.push(Node { bounds, power, kind: Kind::Leaf(0) });
+        let (left, right) = indices.split_at_mut(middle);
+        let left = self.build(emitters, left, trail, depth + 1);
+        let right = self.build(emitters, right, trail | 1 << depth, depth + 1);
+        self.nodes[node].kind = Kind::Interior(left, right);
+        node
+    }
+
+    // power over squared distance, with the distance kept no smaller than
+    // the node's half diagonal so points inside a cluster do not blow up
+    fn importance(&self, node: usize, point: &Point) -> f64 {
+        let node = &self.nodes[node];
+        let (center, extents) = (&node.bounds.center, &node.bounds.extents);
+        let offset = point - center;
+        let distance2 = offset.x * offset.x + offset.y * offset.y + offset.z * offset.z;
+        let radius2 = extents.x * extents.x + extents.y * extents.y + extents.z * extents.z;
+        node.power / distance2.max(radius2).max(f64::EPSILON)
+    }
+
+    fn left_probability(&self, left: usize, right: usize, point: &Point) -> Option<f64> {
+        let (left, right) = (self.importance(left, point), self.importance(right, point));
+        (left + right > 0.).then(|| left / (left + right))
+    }
+
+    // a light index with the probability of picking it from `point`
+    pub fn sample(&self, point: &Point, mut u: f64) -> Option<(usize, f64)> {
+        let mut node = 0;
+        let mut probability = 1.;
+
+        loop {
+            match self.nodes.get(node)?.kind {
+                Kind::Leaf(light) => return Some((light, probability)),
+                Kind::Interior(left, right) => {
+                    let p = self.left_probability(left, right, point)?;
+                    if u < p {
+                        u /= p;
+                        probability *= p;
+                        node = left;
+                    } else {
+                        u = ((u - p) / (1. - p)).min(1. - f64::EPSILON);
+                        probability *= 1. - p;
+                        node = right;
+                    }
+                }
+            }
+        }
+    }
+
+    // probability of `sample` picking `light` from `point`
+    pub fn probability(&self, point: &Point, light: usize) -> f64 {
+        let (trail, depth) = self.trails[light];
+        let mut node = 0;
+        let mut probability = 1.;
+
+        for level in 0..depth {
+            if let Kind::Interior(left, right) = self.nodes[node].kind {
+                let p = match self.left_probability(left, right, point) {
+                    Some(p) => p,
+                    None => return 0.,
+                };
+                if trail >> level & 1 == 0 {
+                    probability *= p;
+                    node = left;
+                } else {
+                    probability *= 1. - p;
+                    node = right;
+                }
+            }
+        }
+
+        probability
+    }
+}
@@ -0,0 +1,106 @@
+mod bvh;
+
+use crate::color::{BLACK,WHITE};
+use crate::geometry::{Point,Trigon,Vector};
+use crate::scene::Scene;
+use bvh::{Emitter,LightTree};
+
+// a face whose material emits light
+pub struct AreaLight {
+    pub face: usize,
+    pub area: f64,
+}
+
+pub struct LightSample {
+    pub face: usize,
+    pub point: Point,
+    // solid angle density as seen from the shading point
+    pub pdf: f64,
+}
+
+// emissive faces, picked through a light tree by their estimated
+// contribution at the shading point
+pub struct Lights {
+    pub lights: Vec<AreaLight>,
+    // position in `lights` of every scene face that emits
+    indices: Vec<Option<usize>>,
+    tree: LightTree,
+}
+
+fn area(trigon: &Trigon) -> f64 {
+    trigon.normal.length / 2.
+}
+
+// converts an area density on `trigon` at `point` to solid angle from `from`;
+// emitters shine from both sides like every other surface
+fn solid_angle_pdf(area_pdf: f64, from: &Point, point: &Point, trigon: &Trigon) -> f64 {
+    let offset = Vector::from(point - from);
+    let distance2 = offset.length * offset.length;
+    let cos = offset.dot_product(&trigon.normal).abs() / (offset.length * trigon.normal.length);
+
+    if cos <= f64::EPSILON {
+        return 0.;
+    }
+
+    area_pdf * distance2 / cos
+}
+
+impl Lights {
+    pub fn new(scene: &Scene) -> Self {
+        let mut lights = vec![];
+        let mut emitters = vec![];
+        let mut indices = vec![None; scene.faces.len()];
+
+        for (face, trigon) in scene.faces.iter().enumerate() {
+            let material = scene.material(trigon);
+            let emits = material.emission != BLACK || material.emission_map.is_some();
+
+            if emits && area(trigon) > 0. {
+                indices[face] = Some(lights.len());
+                lights.push(AreaLight { face, area: area(trigon) });
+
+                // emission maps are counted at their full strength
+                let emission = if material.emission == BLACK { WHITE } else { material.emission };
+                let [p0, p1, p2] = [&trigon.points[0], &trigon.points[1], &trigon.points[2]];
+                emitters.push(Emitter {
+                    min: Point::new(p0.x.min(p1.x).min(p2.x), p0.y.min(p1.y).min(p2.y), p0.z.min(p1.z).min(p2.z)),
+                    max: Point::new(p0.x.max(p1.x).max(p2.x), p0.y.max(p1.y).max(p2.y), p0.z.max(p1.z).max(p2.z)),
+                    power: area(trigon) * emission.luminance().max(0.),
+                });
+            }
+        }
+
+        let tree = LightTree::new(&emitters);
+        Lights { lights, indices, tree }
+    }
+
+    // a point on an emitter, uniform over the area of the one picked
+    pub fn sample(&self, scene: &Scene, from: &Point, u: (f64, f64, f64)) -> Option<LightSample> {
+        let (index, probability) = self.tree.sample(from, u.0)?;
+        let light = &self.lights[index];
+        let trigon = &scene.faces[light.face];
+
+        let root = u.1.sqrt();
+        let (b0, b1) = (1. - root, u.2 * root);
+        let b2 = 1. - b0 - b1;
+        let [p0, p1, p2] = [&trigon.points[0], &trigon.points[1], &trigon.points[2]];
+        let point = Point::new(
+            p0.x * b0 + p1.x * b1 + p2.x * b2,
+            p0.y * b0 + p1.y * b1 + p2.y * b2,
+            p0.z * b0 + p1.z * b1 + p2.z * b2,
+        );
+
+        let pdf = solid_angle_pdf(probability / light.area, from, &point, trigon);
+        (pdf > 0.).then_some(LightSample { face: light.face, point, pdf })
+    }
+
+    // density of `sample` picking `point` on scene face `face`
+    pub fn pdf(&self, scene: &Scene, from: &Point, face: usize, point: &Point) -> f64 {
+        let index = match self.indices[face] {
+            Some(index) => index,
+            None => return 0.,
+        };
+        let light = &self.lights[index];
+        solid_angle_pdf(self.tree.probability(from, index) / light.area, from, point, &scene.faces[face])
+    }
+}
@@ -1,77 +1,222 @@
+mod mtl;
+mod tangents;
+pub mod stl;
+
+use std::collections::HashMap;
 use std::fs;
-use std::path::{Path,PathBuf};
+use std::path::Path;
 use crate::geometry::{Point,Trigon};
+use crate::material::Material;
+use crate::scene::Scene;
+use tangents::Corner;
+
+fn read_file(path: &Path) -> Result<Vec<u8>, String> {
+    fs::read(path)
+        .map_err(|err| format!("Cannot read {}: {}", path.display(), err))
+}
 
-fn read_obj_file(dir: &str, name: &str) -> String {
-    let mut path_buf: PathBuf = Path::new(dir).join(name);
-    path_buf.set_extension("obj");
-    fs::read_to_string(path_buf.to_str().unwrap()).unwrap()
+fn parse_number(value: Option<&&str>, line: &str) -> Result<f64, String> {
+    value
+        .and_then(|value| value.parse().ok())
+        .ok_or_else(|| format!("Invalid vertex: {}", line))
 }
 
-fn parse_vertex(line: &str) -> Point {
-    let data: Vec<&str> = line.split(' ').collect();
-    let x: f64 = data[1].parse().unwrap();
-    let y: f64 = data[2].parse().unwrap();
-    let z: f64 = data[3].parse().unwrap();
-    Point::new(x, y, z)
+fn parse_vertex(line: &str) -> Result<Point, String> {
+    let data: Vec<&str> = line.split_whitespace().collect();
+    let x = parse_number(data.get(1), line)?;
+    let y = parse_number(data.get(2), line)?;
+    let z = parse_number(data.get(3), line)?;
+    Ok(Point::new(x, y, z))
 }
 
-fn parse_vertex_lines(lines: Vec<&str>) -> Vec<Point> {
+fn parse_vertex_lines(lines: Vec<&str>) -> Result<Vec<Point>, String> {
     lines
         .iter()
         .map(|line| parse_vertex(line))
         .collect()
 }
 
-fn parse_face_vertex_data(data: &str) -> usize {
-    let params: Vec<&str> = data.split('/').collect();
-    params[0].parse().unwrap()
+fn parse_texture_coordinate(line: &str) -> Result<(f64, f64), String> {
+    let data: Vec<&str> = line.split_whitespace().collect();
+    let parse = |value: Option<&&str>| {
+        value
+            .and_then(|value| value.parse().ok())
+            .ok_or_else(|| format!("Invalid texture coordinate: {}", line))
+    };
+    let u = parse(data.get(1))?;
+    // the v coordinate is optional for 1D textures
+    let v = if data.len() > 2 { parse(data.get(2))? } else { 0. };
+    Ok((u, v))
 }
 
-fn parse_face(line: &str) -> (usize, usize, usize) {
-    let data: Vec<&str> = line.split(' ').collect();
-    let v1 = parse_face_vertex_data(data[1]);
-    let v2 = parse_face_vertex_data(data[2]);
-    let v3 = parse_face_vertex_data(data[3]);
-    (v1 - 1, v2 - 1, v3 - 1)
+fn parse_index(
+    value: Option<&&str>,
+    line: &str,
+    count: usize,
+    kind: &str,
+) -> Result<usize, String> {
+    let index: usize = value
+        .and_then(|index| index.parse().ok())
+        .ok_or_else(|| format!("Invalid face: {}", line))?;
+
+    if index == 0 || index > count {
+        return Err(format!("Face references missing {} {}: {}", kind, index, line));
+    }
+
+    Ok(index - 1)
 }
 
+// vertex index and optional texture coordinate index of `v/vt/vn`
+fn parse_face_vertex_data(
+    data: Option<&&str>,
+    line: &str,
+    counts: (usize, usize),
+) -> Result<Corner, String> {
+    let params: Vec<&str> = data.map_or(vec![], |data| data.split('/').collect());
+    let vertex = parse_index(params.first(), line, counts.0, "vertex")?;
+    let uv = match params.get(1).filter(|index| !index.is_empty()) {
+        Some(index) => Some(parse_index(Some(index), line, counts.1, "texture coordinate")?),
+        None => None,
+    };
+
+    Ok((vertex, uv))
+}
+
+fn parse_face(
+    line: &str,
+    counts: (usize, usize),
+) -> Result<[Corner; 3], String> {
+    let data: Vec<&str> = line.split_whitespace().collect();
+    let v1 = parse_face_vertex_data(data.get(1), line, counts)?;
+    let v2 = parse_face_vertex_data(data.get(2), line, counts)?;
+    let v3 = parse_face_vertex_data(data.get(3), line, counts)?;
+    Ok([v1, v2, v3])
+}
+
+// face line with the material and object in effect where it appears
+type FaceLine<'l> = (&'l str, usize, usize);
+
 fn parse_face_lines<'a>(
-    lines: Vec<&str>,
-    vertices: Vec<Point>
-) -> Vec<Trigon<'a>> {
+    lines: Vec<FaceLine>,
+    vertices: Vec<Point>,
+    uvs: Vec<(f64, f64)>,
+) -> Result<Vec<(Trigon<'a>, [Corner; 3])>, String> {
     lines
         .iter()
-        .map(|line| {
-            let (v1, v2, v3) = parse_face(line);
-            let p1 = vertices[v1].clone();
-            let p2 = vertices[v2].clone();
-            let p3 = vertices[v3].clone();
-            Trigon::new(p1, p2, p3)
+        .map(|(line, material, object)| {
+            let corners = parse_face(line, (vertices.len(), uvs.len()))?;
+            let [v1, v2, v3] = corners;
+            let p1 = vertices[v1.0].clone();
+            let p2 = vertices[v2.0].clone();
+            let p3 = vertices[v3.0].clone();
+            let mut trigon = Trigon::new(p1, p2, p3);
+            trigon.material = *material;
+            trigon.object = *object;
+            if let (Some(t1), Some(t2), Some(t3)) = (v1.1, v2.1, v3.1) {
+                trigon.uvs = Some([uvs[t1], uvs[t2], uvs[t3]]);
+            }
+            Ok((trigon, corners))
         })
         .collect()
 }
 
-fn parse_obj_data<'a>(data: String) -> Vec<Trigon<'a>> {
+fn statement_argument<'a>(line: &'a str, keyword: &str) -> &'a str {
+    line[keyword.len()..].trim()
+}
+
+fn load_material_libraries(
+    line: &str,
+    dir: &Path,
+    materials: &mut Vec<Material>,
+) {
+    for name in statement_argument(line, "mtllib").split_whitespace() {
+        match mtl::fetch_materials(&dir.join(name)) {
+            Ok(library) => materials.extend(library),
+            Err(err) => eprintln!("warning: {}", err),
+        }
+    }
+}
+
+// `o` names objects and `g` groups within them; either may stand alone
+fn object_name(object: Option<&str>, group: Option<&str>) -> String {
+    match (object, group) {
+        (Some(object), Some(group)) => format!("{}/{}", object, group),
+        (Some(name), None) | (None, Some(name)) => name.to_string(),
+        (None, None) => "default".to_string(),
+    }
+}
+
+fn parse_obj_data<'a>(data: String, dir: &Path) -> Result<Scene<'a>, String> {
     let mut vertex_lines: Vec<&str>  = vec![];
-    let mut face_lines: Vec<&str> = vec![];
+    let mut uv_lines: Vec<&str> = vec![];
+    let mut face_lines: Vec<FaceLine> = vec![];
+    let mut materials: Vec<Material> = vec![Material::default()];
+    let mut current_material = 0;
+    let mut objects: Vec<String> = vec![];
+    let mut object_indices: HashMap<String, usize> = HashMap::new();
+    let mut object: Option<&str> = None;
+    let mut group: Option<&str> = None;
+    let mut current_object = None;
 
     for line in data.lines() {
         if line.starts_with("v ") {
             vertex_lines.push(line);
+        } else if line.starts_with("vt ") {
+            uv_lines.push(line);
         } else if line.starts_with("f ") {
-            face_lines.push(line);
+            let index = *current_object.get_or_insert_with(|| {
+                let name = object_name(object, group);
+                *object_indices.entry(name.clone()).or_insert_with(|| {
+                    objects.push(name);
+                    objects.len() - 1
+                })
+            });
+            face_lines.push((line, current_material, index));
+        } else if line.starts_with("mtllib ") {
+            load_material_libraries(line, dir, &mut materials);
+        } else if line.starts_with("o ") {
+            object = Some(statement_argument(line, "o")).filter(|name| !name.is_empty());
+            group = None;
+            current_object = None;
+        } else if line.starts_with("g ") {
+            group = Some(statement_argument(line, "g")).filter(|name| !name.is_empty());
+            current_object = None;
+        } else if line.starts_with("usemtl ") {
+            let name = statement_argument(line, "usemtl");
+            current_material = materials
+                .iter()
+                .rposition(|material| material.name == name)
+                .unwrap_or_else(|| {
+                    eprintln!("warning: Unknown material '{}'", name);
+                    0
+                });
         }
     }
 
-    let vertices = parse_vertex_lines(vertex_lines);
-    let faces = parse_face_lines(face_lines, vertices);
+    let vertices = parse_vertex_lines(vertex_lines)?;
+    let uvs = uv_lines
+        .iter()
+        .map(|line| parse_texture_coordinate(line))
+        .collect::<Result<Vec<_>, _>>()?;
+    let (mut faces, corners): (Vec<_>, Vec<_>) = parse_face_lines(face_lines, vertices, uvs)?
+        .into_iter()
+        .unzip();
+    tangents::generate(&mut faces, &corners);
 
-    faces
+    Ok(Scene::new(faces, materials, objects))
 }
 
+// STL or OBJ, told apart by content whatever the file is called
+pub fn fetch_object<'a>(path: &Path, stl_settings: &stl::Settings) -> Result<Scene<'a>, String> {
+    let data = read_file(path)?;
 
-pub fn fetch_object<'a>(dir: &str, name: &str) -> Vec<Trigon<'a>> {
-    let data: String = read_obj_file(dir, name);
-    parse_obj_data(data)
-}
\ No newline at end of file
+    if let Some(format) = stl::detect(&data) {
+        return stl::parse_stl_data(&data, format, stl_settings)
+            .map_err(|err| format!("{}: {}", path.display(), err));
+    }
+
+    let data = String::from_utf8(data)
+        .map_err(|_| format!("{} is neither an OBJ nor an STL file", path.display()))?;
+    let dir = path.parent().unwrap_or_else(|| Path::new(""));
+    parse_obj_data(data, dir)
+}
@@ -0,0 +1,275 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path,PathBuf};
+use std::sync::Arc;
+use crate::color::Color;
+use crate::material::Material;
+use crate::texture::{ImageTexture,MipMap,Pattern,Procedural,Space,Texture,Wrap,read_image};
+
+// images shared by several maps are decoded once
+type ImageCache = HashMap<(PathBuf, bool), Arc<MipMap>>;
+
+fn parse_numbers(line: &str, count: usize) -> Result<Vec<f64>, String> {
+    let values: Vec<f64> = line
+        .split_whitespace()
+        .skip(1)
+        .take(count)
+        .map(|value| value.parse())
+        .collect::<Result<_, _>>()
+        .map_err(|_| format!("Invalid material statement: {}", line))?;
+
+    if values.is_empty() {
+        return Err(format!("Invalid material statement: {}", line));
+    }
+
+    Ok(values)
+}
+
+fn parse_color(line: &str) -> Result<Color, String> {
+    let values = parse_numbers(line, 3)?;
+
+    // a single value sets all three channels
+    match values.len() {
+        3 => Ok(Color::new(values[0], values[1], values[2])),
+        _ => Ok(Color::gray(values[0])),
+    }
+}
+
+fn parse_number(line: &str) -> Result<f64, String> {
+    Ok(parse_numbers(line, 1)?[0])
+}
+
+fn parse_fraction(line: &str) -> Result<f64, String> {
+    Ok(parse_number(line)?.clamp(0., 1.))
+}
+
+enum Source {
+    File(PathBuf),
+    Procedural(Pattern),
+}
+
+struct Map {
+    source: Source,
+    wrap: Wrap,
+    scale: [f64; 3],
+    offset: [f64; 3],
+    space: Space,
+    colors: Option<[Color; 2]>,
+    octaves: Option<u32>,
+    multiplier: f64,
+}
+
+fn numbers<'a, I: Iterator<Item = &'a str>>(tokens: &mut std::iter::Peekable<I>) -> Vec<f64> {
+    let mut values = vec![];
+    while let Some(value) = tokens.next_if(|token| token.parse::<f64>().is_ok()) {
+        values.extend(value.parse::<f64>().ok());
+    }
+    values
+}
+
+// `map_* [-options] file` or `map_* -procedural <pattern> [-options]`;
+// standard options we do not use are skipped
+fn parse_map(line: &str, dir: &Path) -> Result<Map, String> {
+    let error = || format!("Invalid texture map: {}", line);
+    let mut tokens = line.split_whitespace().skip(1).peekable();
+    let mut pattern = None;
+    let mut map = Map {
+        source: Source::File(PathBuf::new()),
+        wrap: Wrap::Repeat,
+        scale: [1.; 3],
+        offset: [0.; 3],
+        space: Space::Object,
+        colors: None,
+        octaves: None,
+        multiplier: 1.,
+    };
+
+    while let Some(option) = tokens.next_if(|token| token.starts_with('-')) {
+        match option {
+            "-clamp" => {
+                map.wrap = match tokens.next() {
+                    Some("on") => Wrap::Clamp,
+                    Some("off") => Wrap::Repeat,
+                    _ => return Err(error()),
+                }
+            }
+            "-wrap" => {
+                map.wrap = tokens.next().and_then(Wrap::from_name).ok_or_else(error)?;
+            }
+            "-o" | "-s" | "-t" => {
+                let values = numbers(&mut tokens);
+                let last = *values.last().ok_or_else(error)?;
+                // missing components repeat the last one given
+                let vector = [0, 1, 2].map(|axis| values.get(axis).copied().unwrap_or(last));
+                match option {
+                    "-o" => map.offset = vector,
+                    "-s" => map.scale = vector,
+                    _ => {}
+                }
+            }
+            "-procedural" => {
+                pattern = Some(tokens.next().and_then(Pattern::from_name).ok_or_else(error)?);
+            }
+            "-space" => {
+                map.space = tokens.next().and_then(Space::from_name).ok_or_else(error)?;
+            }
+            "-colors" => {
+                let values = numbers(&mut tokens);
+                if values.len() != 6 {
+                    return Err(error());
+                }
+                map.colors = Some([
+                    Color::new(values[0], values[1], values[2]),
+                    Color::new(values[3], values[4], values[5]),
+                ]);
+            }
+            "-octaves" => {
+                let octaves = tokens.next().and_then(|value| value.parse().ok());
+                map.octaves = Some(octaves.filter(|&octaves| octaves > 0).ok_or_else(error)?);
+            }
+            "-mm" => {
+                tokens.next();
+                tokens.next();
+            }
+            "-bm" => {
+                map.multiplier = tokens.next().and_then(|value| value.parse().ok()).ok_or_else(error)?;
+            }
+            "-blendu" | "-blendv" | "-boost" | "-cc" | "-imfchan" | "-texres" | "-type" => {
+                tokens.next();
+            }
+            _ => return Err(error()),
+        }
+    }
+
+    let name = tokens.collect::<Vec<_>>().join(" ").replace('\\', "/");
+
+    map.source = match pattern {
+        Some(pattern) if name.is_empty() => Source::Procedural(pattern),
+        None if !name.is_empty() => Source::File(dir.join(name)),
+        _ => return Err(error()),
+    };
+
+    Ok(map)
+}
+
+// color maps are stored sRGB encoded, scalar maps linear
+fn load_map(map: Map, srgb: bool, cache: &mut ImageCache) -> Option<Arc<dyn Texture>> {
+    let path = match map.source {
+        Source::File(path) => path,
+        Source::Procedural(pattern) => {
+            let mut procedural = Procedural::new(pattern);
+            procedural.space = map.space;
+            procedural.scale = map.scale;
+            procedural.offset = map.offset;
+            procedural.colors = map.colors.unwrap_or(procedural.colors);
+            procedural.octaves = map.octaves.unwrap_or(procedural.octaves);
+            return Some(Arc::new(procedural));
+        }
+    };
+
+    let image = match cache.get(&(path.clone(), srgb)) {
+        Some(image) => image.clone(),
+        None => match read_image(&path, srgb) {
+            Ok(image) => {
+                let image = Arc::new(image);
+                cache.insert((path, srgb), image.clone());
+                image
+            }
+            Err(err) => {
+                eprintln!("warning: {}", err);
+                return None;
+            }
+        },
+    };
+
+    Some(Arc::new(ImageTexture {
+        image,
+        wrap: map.wrap,
+        scale: (map.scale[0], map.scale[1]),
+        offset: (map.offset[0], map.offset[1]),
+    }))
+}
+
+fn finish(material: &mut Material, specular: Color, illum: u32) {
+    if (3..=7).contains(&illum) {
+        material.reflection = specular;
+    }
+}
+
+fn parse_mtl_data(data: &str, dir: &Path) -> Result<Vec<Material>, String> {
+    let mut materials: Vec<Material> = vec![];
+    let mut images = ImageCache::new();
+    let mut specular = Color::gray(0.);
+    let mut illum = 2;
+
+    for line in data.lines() {
+        let line = line.trim();
+        let keyword = line.split_whitespace().next().unwrap_or("");
+
+        if keyword == "newmtl" {
+            if let Some(material) = materials.last_mut() {
+                finish(material, specular, illum);
+            }
+            let name = line["newmtl".len()..].trim();
+            materials.push(Material::new(name));
+            specular = Color::gray(0.);
+            illum = 2;
+            continue;
+        }
+
+        let material = match materials.last_mut() {
+            Some(material) => material,
+            None => continue,
+        };
+
+        match keyword {
+            "Kd" => material.diffuse = parse_color(line)?,
+            "Ks" => {
+                specular = parse_color(line)?;
+                material.specular = specular;
+            }
+            "Ns" => material.shininess = parse_number(line)?,
+            "Pr" => material.roughness = Some(parse_fraction(line)?),
+            "Pm" => material.metallic = Some(parse_fraction(line)?),
+            "Ps" => material.sheen = Some(parse_fraction(line)?),
+            "Pc" => material.clearcoat = Some(parse_fraction(line)?),
+            "Pcr" => material.clearcoat_roughness = Some(parse_fraction(line)?),
+            "Ke" => material.emission = parse_color(line)?,
+            "map_Kd" => material.diffuse_map = load_map(parse_map(line, dir)?, true, &mut images),
+            "map_Ks" => material.specular_map = load_map(parse_map(line, dir)?, true, &mut images),
+            "map_Pr" => material.roughness_map = load_map(parse_map(line, dir)?, false, &mut images),
+            "map_Pm" => material.metallic_map = load_map(parse_map(line, dir)?, false, &mut images),
+            "map_Ps" => material.sheen_map = load_map(parse_map(line, dir)?, false, &mut images),
+            "map_Pc" => material.clearcoat_map = load_map(parse_map(line, dir)?, false, &mut images),
+            "map_d" => material.opacity_map = load_map(parse_map(line, dir)?, false, &mut images),
+            "map_Ke" => material.emission_map = load_map(parse_map(line, dir)?, true, &mut images),
+            "norm" | "map_Kn" => {
+                material.normal_map = load_map(parse_map(line, dir)?, false, &mut images);
+            }
+            "bump" | "map_bump" | "map_Bump" => {
+                let map = parse_map(line, dir)?;
+                material.bump_scale = map.multiplier;
+                material.bump_map = load_map(map, false, &mut images);
+            }
+            "Tf" => material.transmission = parse_color(line)?,
+            "Ni" => material.ior = parse_number(line)?,
+            "d" => material.transparency = 1. - parse_number(line)?,
+            "Tr" => material.transparency = parse_number(line)?,
+            "illum" => illum = parse_number(line)? as u32,
+            _ => {}
+        }
+    }
+
+    if let Some(material) = materials.last_mut() {
+        finish(material, specular, illum);
+    }
+
+    Ok(materials)
+}
+
+pub fn fetch_materials(path: &Path) -> Result<Vec<Material>, String> {
+    let data = fs::read_to_string(path)
+        .map_err(|err| format!("Cannot read {}: {}", path.display(), err))?;
+    let dir = path.parent().unwrap_or_else(|| Path::new(""));
+    parse_mtl_data(&data, dir)
+}
@@ -0,0 +1,305 @@
+use std::collections::HashMap;
+use std::convert::TryInto;
+use crate::geometry::{Point,Trigon};
+use crate::material::Material;
+use crate::scene::Scene;
+
+const HEADER: usize = 80;
+const FACET: usize = 50;
+
+// fraction of the model's bounding box diagonal under which corners snap together
+const DEFAULT_WELD: f64 = 1e-6;
+
+// STL facets share no vertices, so nearly coincident corners are snapped
+// together to close cracks; facet normals are redundant with the winding and
+// only consulted on request
+#[derive(Debug,Clone,Default)]
+pub struct Settings {
+    // flip facets whose winding disagrees with their stored normal
+    pub facet_normals: bool,
+    // distance under which corners snap together, a millionth of the model size if unset
+    pub weld_tolerance: Option<f64>,
+}
+
+// a solid name with its facets as stored normal and corners
+type Solid = (String, Vec<([f64; 3], [[f64; 3]; 3])>);
+
+// the two encodings, told apart once by `detect`
+pub enum Format<'d> {
+    Ascii(&'d str),
+    Binary(usize),
+}
+
+// an 80 byte header and a facet count with that many 50 byte facets after it
+// make a binary file, even one whose header starts with `solid` as some
+// exporters write; text needs that many facets to fill gigabytes, so it never
+// passes for binary
+pub fn detect(data: &[u8]) -> Option<Format<'_>> {
+    if data.len() >= HEADER + 4 {
+        let count = u32::from_le_bytes(data[HEADER..HEADER + 4].try_into().unwrap()) as usize;
+        if data.len() >= HEADER + 4 + count * FACET {
+            return Some(Format::Binary(count));
+        }
+    }
+
+    let text = std::str::from_utf8(data).ok()?;
+    let start = text.trim_start();
+    if start.starts_with("solid") && (start.contains("facet") || start.contains("endsolid")) {
+        Some(Format::Ascii(text))
+    } else {
+        None
+    }
+}
+
+fn parse_numbers(words: &[&str], line: &str, kind: &str) -> Result<[f64; 3], String> {
+    let number = |index: usize| {
+        words
+            .get(index)
+            .and_then(|word| word.parse().ok())
+            .ok_or_else(|| format!("Invalid STL {}: {}", kind, line.trim()))
+    };
+    Ok([number(0)?, number(1)?, number(2)?])
+}
+
+fn parse_ascii(text: &str) -> Result<Vec<Solid>, String> {
+    let mut solids: Vec<Solid> = vec![];
+    let mut normal = [0.; 3];
+    let mut corners: Vec<[f64; 3]> = vec![];
+
+    for line in text.lines() {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.first() {
+            Some(&"solid") => solids.push((words[1..].join(" "), vec![])),
+            Some(&"facet") => {
+                if words.get(1) != Some(&"normal") {
+                    return Err(format!("Invalid STL facet: {}", line.trim()));
+                }
+                normal = parse_numbers(&words[2..], line, "normal")?;
+                corners.clear();
+            }
+            Some(&"vertex") => corners.push(parse_numbers(&words[1..], line, "vertex")?),
+            Some(&"endfacet") => {
+                let corners: [[f64; 3]; 3] = corners
+                    .as_slice()
+                    .try_into()
+                    .map_err(|_| format!("STL facet with {} vertices", corners.len()))?;
+                match solids.last_mut() {
+                    Some((_, facets)) => facets.push((normal, corners)),
+                    None => return Err("STL facet outside a solid".to_string()),
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(solids)
+}
+
+fn parse_binary(data: &[u8], count: usize) -> Result<Vec<Solid>, String> {
+    let float = |bytes: &[u8], index: usize| {
+        f32::from_le_bytes(bytes[index * 4..index * 4 + 4].try_into().unwrap()) as f64
+    };
+    let vector = |bytes: &[u8], first: usize| [float(bytes, first), float(bytes, first + 1), float(bytes, first + 2)];
+
+    let end = HEADER + 4 + count * FACET;
+    if data.len() < end {
+        return Err(format!("STL data ends before the last of its {} facets", count));
+    }
+    if data.len() > end {
+        eprintln!("warning: Ignored {} bytes after the last STL facet", data.len() - end);
+    }
+
+    let facets = data[HEADER + 4..end]
+        .chunks_exact(FACET)
+        .map(|facet| (vector(facet, 0), [vector(facet, 3), vector(facet, 6), vector(facet, 9)]))
+        .collect();
+
+    // the header is free text, often an exporter banner, so it names nothing
+    Ok(vec![(String::new(), facets)])
+}
+
+// merges corners closer than `tolerance`, looking through the neighboring
+// grid cells so pairs straddling a cell boundary still meet
+fn weld(solids: &[Solid], tolerance: f64) -> (Vec<[f64; 3]>, Vec<[usize; 3]>) {
+    let mut vertices: Vec<[f64; 3]> = vec![];
+    let mut cells: HashMap<[i64; 3], Vec<usize>> = HashMap::new();
+    let cell = |position: &[f64; 3]| position.map(|value| (value / tolerance).floor() as i64);
+
+    let mut index = |position: &[f64; 3]| {
+        let [x, y, z] = cell(position);
+        for dx in -1..=1 {
+            for dy in -1..=1 {
+                for dz in -1..=1 {
+                    let found = cells.get(&[x + dx, y + dy, z + dz]).and_then(|candidates| {
+                        candidates.iter().copied().find(|candidate| {
+                            let other = &vertices[*candidate];
+                            let distance: f64 = (0..3).map(|axis| (other[axis] - position[axis]).powi(2)).sum();
+                            distance <= tolerance * tolerance
+                        })
+                    });
+                    if let Some(found) = found {
+                        return found;
+                    }
+                }
+            }
+        }
+
+        vertices.push(*position);
+        cells.entry([x, y, z]).or_default().push(vertices.len() - 1);
+        vertices.len() - 1
+    };
+
+    let triangles = solids
+        .iter()
+        .flat_map(|(_, facets)| facets)
+        .map(|(_, corners)| [index(&corners[0]), index(&corners[1]), index(&corners[2])])
+        .collect();
+
+    (vertices, triangles)
+}
+
+fn model_size(solids: &[Solid]) -> f64 {
+    let mut min = [f64::INFINITY; 3];
+    let mut max = [f64::NEG_INFINITY; 3];
+    for (_, facets) in solids {
+        for corner in facets.iter().flat_map(|(_, corners)| corners) {
+            for axis in 0..3 {
+                min[axis] = min[axis].min(corner[axis]);
+                max[axis] = max[axis].max(corner[axis]);
+            }
+        }
+    }
+    (0..3).map(|axis| (max[axis] - min[axis]).powi(2)).sum::<f64>().sqrt()
+}
+
+pub fn parse_stl_data<'a>(data: &[u8], format: Format, settings: &Settings) -> Result<Scene<'a>, String> {
+    let solids = match format {
+        Format::Ascii(text) => parse_ascii(text)?,
+        Format::Binary(count) => parse_binary(data, count)?,
+    };
+
+    let tolerance = settings.weld_tolerance.unwrap_or_else(|| model_size(&solids) * DEFAULT_WELD);
+    let (vertices, triangles) = weld(&solids, tolerance.max(1e-9));
+
+    let mut objects: Vec<String> = vec![];
+    let mut faces: Vec<Trigon> = vec![];
+    let mut degenerate = 0;
+    let mut triangles = triangles.into_iter();
+
+    for (name, facets) in &solids {
+        let name = if name.is_empty() { "default" } else { name.as_str() };
+        let object = objects.iter().position(|other| other == name).unwrap_or_else(|| {
+            objects.push(name.to_string());
+            objects.len() - 1
+        });
+
+        for ((normal, _), [a, b, c]) in facets.iter().zip(triangles.by_ref()) {
+            // welding can pull corners of a sliver together
+            if a == b || b == c || a == c {
+                degenerate += 1;
+                continue;
+            }
+
+            let point = |index: usize| Point::new(vertices[index][0], vertices[index][1], vertices[index][2]);
+            let mut trigon = Trigon::new(point(a), point(b), point(c));
+            let [nx, ny, nz] = *normal;
+            let facing = trigon.normal.x * nx + trigon.normal.y * ny + trigon.normal.z * nz;
+            if settings.facet_normals && facing < 0. {
+                trigon = Trigon::new(point(a), point(c), point(b));
+            }
+            trigon.object = object;
+            faces.push(trigon);
+        }
+    }
+
+    if degenerate > 0 {
+        eprintln!("warning: Skipped {} degenerate STL facets", degenerate);
+    }
+
+    Ok(Scene::new(faces, vec![Material::default()], objects))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn binary(header: &[u8], facets: &[[[f32; 3]; 3]]) -> Vec<u8> {
+        let mut data = header.to_vec();
+        data.resize(HEADER, b' ');
+        data.extend_from_slice(&(facets.len() as u32).to_le_bytes());
+        for corners in facets {
+            data.extend_from_slice(&[0; 12]);
+            for value in corners.iter().flatten() {
+                data.extend_from_slice(&value.to_le_bytes());
+            }
+            data.extend_from_slice(&[0; 2]);
+        }
+        data
+    }
+
+    fn parse(data: &[u8]) -> Result<Scene<'static>, String> {
+        let format = detect(data).ok_or("not detected as STL")?;
+        parse_stl_data(data, format, &Settings::default())
+    }
+
+    const TWO_SOLIDS: &str = "\
+solid first part
+  facet normal 0 0 1
+    outer loop
+      vertex 0 0 0
+      vertex 1 0 0
+      vertex 0 1 0
+    endloop
+  endfacet
+endsolid first part
+solid second
+  facet normal 0 0 1
+    outer loop
+      vertex 0 0 1
+      vertex 1 0 1
+      vertex 0 1 1
+    endloop
+  endfacet
+endsolid second
+";
+
+    #[test]
+    fn ascii_solids_become_objects() {
+        let scene = parse(TWO_SOLIDS.as_bytes()).unwrap();
+        assert_eq!(scene.objects, ["first part", "second"]);
+        assert_eq!(scene.faces.len(), 2);
+        assert_eq!((scene.faces[0].object, scene.faces[1].object), (0, 1));
+    }
+
+    #[test]
+    fn binary_header_may_start_with_solid() {
+        let data = binary(b"solid exported by some tool", &[[[0., 0., 0.], [1., 0., 0.], [0., 1., 0.]]]);
+        assert!(matches!(detect(&data), Some(Format::Binary(1))));
+        assert_eq!(parse(&data).unwrap().faces.len(), 1);
+    }
+
+    #[test]
+    fn malformed_data_is_an_error() {
+        let data = binary(b"solid", &[[[0., 0., 0.], [1., 0., 0.], [0., 1., 0.]]; 2]);
+        assert!(parse_stl_data(&data[..data.len() - 10], Format::Binary(2), &Settings::default()).is_err());
+        assert!(parse(&data[..data.len() - 10]).is_err());
+
+        let two_vertices = TWO_SOLIDS.replacen("      vertex 0 1 0\n", "", 1);
+        assert!(parse(two_vertices.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn shared_edge_welds_to_four_vertices() {
+        let jitter = 1e-9;
+        let solids: Vec<Solid> = vec![(
+            String::new(),
+            vec![
+                ([0., 0., 1.], [[0., 0., 0.], [1., 0., 0.], [1., 1., 0.]]),
+                ([0., 0., 1.], [[jitter, 0., 0.], [1., 1. + jitter, 0.], [0., 1., 0.]]),
+            ],
+        )];
+        let (vertices, triangles) = weld(&solids, 1e-6);
+        assert_eq!(vertices.len(), 4);
+        assert_eq!(triangles, [[0, 1, 2], [0, 2, 3]]);
+    }
+}
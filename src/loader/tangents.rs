@@ -0,0 +1,88 @@
+use std::collections::HashMap;
+use crate::geometry::{Trigon,Vector};
+
+// vertex and texture coordinate indices of a face corner
+pub type Corner = (usize, Option<usize>);
+
+struct Frame {
+    tangent: Vector<'static>,
+    bitangent: Vector<'static>,
+    normal: Vector<'static>,
+}
+
+fn add(a: &Vector, b: &Vector, weight: f64) -> Vector<'static> {
+    Vector::new(a.x + b.x * weight, a.y + b.y * weight, a.z + b.z * weight)
+}
+
+// angle between the two edges leaving `corner`
+fn corner_angle(trigon: &Trigon, corner: usize) -> f64 {
+    let point = &trigon.points[corner];
+    let next = Vector::from(&trigon.points[(corner + 1) % 3] - point);
+    let previous = Vector::from(&trigon.points[(corner + 2) % 3] - point);
+    let cos = next.dot_product(&previous) / (next.length * previous.length);
+    if cos.is_nan() { 0. } else { cos.clamp(-1., 1.).acos() }
+}
+
+fn face_frame(trigon: &Trigon) -> Option<Frame> {
+    if trigon.normal.length < f64::EPSILON {
+        return None;
+    }
+
+    let (tangent, bitangent) = trigon.position_derivatives()?;
+    Some(Frame { tangent, bitangent, normal: trigon.normal.normalize() })
+}
+
+// per-vertex tangents in the MikkTSpace convention: face tangents are
+// accumulated with corner angle weights over corners sharing a position and
+// texture coordinate, made orthogonal to the averaged normal and stored with
+// the handedness sign so that bitangent = sign * normal x tangent
+pub fn generate(faces: &mut [Trigon], corners: &[[Corner; 3]]) {
+    let frames: Vec<Option<Frame>> = faces.iter().map(face_frame).collect();
+    let mut sums: HashMap<(usize, usize), Frame> = HashMap::new();
+
+    for (index, frame) in frames.iter().enumerate() {
+        let frame = match frame {
+            Some(frame) => frame,
+            None => continue,
+        };
+
+        for (corner, &(vertex, uv)) in corners[index].iter().enumerate() {
+            let uv = match uv {
+                Some(uv) => uv,
+                None => continue,
+            };
+            let weight = corner_angle(&faces[index], corner);
+            let zero = || Frame {
+                tangent: Vector::new(0., 0., 0.),
+                bitangent: Vector::new(0., 0., 0.),
+                normal: Vector::new(0., 0., 0.),
+            };
+            let sum = sums.entry((vertex, uv)).or_insert_with(zero);
+            sum.tangent = add(&sum.tangent, &frame.tangent, weight);
+            sum.bitangent = add(&sum.bitangent, &frame.bitangent, weight);
+            sum.normal = add(&sum.normal, &frame.normal, weight);
+        }
+    }
+
+    for (index, trigon) in faces.iter_mut().enumerate() {
+        if frames[index].is_none() {
+            continue;
+        }
+
+        let tangents = corners[index].map(|(vertex, uv)| {
+            let sum = &sums[&(vertex, uv?)];
+            let normal: Vector = sum.normal.normalize();
+            let tangent = add(&sum.tangent, &normal, -normal.dot_product(&sum.tangent));
+            if tangent.length < f64::EPSILON {
+                return None;
+            }
+            let tangent: Vector = tangent.normalize();
+            let sign = if normal.cross_product(&tangent).dot_product(&sum.bitangent) < 0. { -1. } else { 1. };
+            Some([tangent.x, tangent.y, tangent.z, sign])
+        });
+
+        if let [Some(t1), Some(t2), Some(t3)] = tangents {
+            trigon.tangents = Some([t1, t2, t3]);
+        }
+    }
+}
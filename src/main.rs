@@ -3,37 +3,153 @@ mod loader;
 mod tree;
 mod canvas;
 mod tracing;
+mod cli;
+mod output;
+mod color;
+mod framebuffer;
+mod tonemap;
+mod material;
+mod scene;
+mod sampling;
+mod integrator;
+mod bsdf;
+mod texture;
+mod environment;
+mod lights;
+mod aov;
+mod denoise;
 
 use std::env;
-use tracing::trace;
-use geometry::Point;
+use std::process;
+use tracing::{Job,Render,heatmap,trace};
 use loader::fetch_object;
+use cli::{Command,Options};
+use output::{Depth,Format};
+use aov::Layer;
+use aov::cryptomatte;
 
-const OBJECTS_DIR: &str = "data/obj";
-const IMAGES_DIR: &str = "data/img";
+// the image, its AOVs and mattes, in one EXR when possible
+fn save(options: &Options, render: &Render, metadata: &[(String, String)]) -> Result<(), String> {
+    let denoised;
+    let framebuffer = match &options.denoise {
+        Some(settings) => {
+            if settings.keep_noisy {
+                let path = output::layer_path(&options.output, "noisy");
+                output::write(&path, &render.framebuffer, &options.image)?;
+            }
+            denoised = denoise::denoise(render, settings, options.threads)?;
+            &denoised
+        }
+        None => &render.framebuffer,
+    };
 
-fn argv() -> Vec<String> {
-    let mut argv: Vec<String> = vec![];
+    // guides rendered only for the denoiser stay out of the files
+    let layers: Vec<Layer> = render
+        .layers
+        .iter()
+        .filter(|layer| options.aovs.iter().any(|aov| aov.name() == layer.name))
+        .cloned()
+        .collect();
 
-    for arg in env::args() {
-        argv.push(arg);
+    if options.image.format == Format::Exr && !options.aov_separate {
+        let layers: Vec<Layer> = layers.into_iter().chain(render.mattes.iter().cloned()).collect();
+        return output::write_layers(&options.output, framebuffer, &layers, metadata, &options.image);
     }
 
-    argv
+    output::write(&options.output, framebuffer, &options.image)?;
+    let data = options.image.for_data();
+    for layer in &layers {
+        let path = output::layer_path(&options.output, &layer.name);
+        output::write(&path, &layer.framebuffer, &data)?;
+    }
+
+    // mattes need their metadata and float IDs, so they always go to EXR
+    if !render.mattes.is_empty() {
+        let path = output::layer_path(&options.output.with_extension("exr"), "cryptomatte");
+        let settings = output::Settings { format: Format::Exr, depth: Depth::ThirtyTwo, ascii: false, ..data };
+        output::write_layers(&path, framebuffer, &render.mattes, metadata, &settings)?;
+    }
+
+    Ok(())
 }
 
-fn main() {
-    let argv = argv();
-    let object_name = &argv[1];
-    let width: u32 = argv[2].parse().unwrap();
-    let height: u32 = argv[3].parse().unwrap();
+fn render(options: &Options) -> Result<(), String> {
+    let scene = fetch_object(&options.input, &options.stl)?;
 
-    let camera_pos = Point::new(0., -3., 0.);
-    let light_pos = Point::new(1.5, -1.5, 1.5);
-    let faces = fetch_object(OBJECTS_DIR, object_name.as_str());
+    if scene.faces.is_empty() {
+        return Err(format!("{} contains no faces", options.input.display()));
+    }
+
+    let environment = options.environment.build()?;
+    let metadata: Vec<(String, String)> = options
+        .cryptomatte
+        .iter()
+        .flat_map(|matte| cryptomatte::metadata(*matte, &scene))
+        .collect();
+
+    let clamped: Vec<&str> = options.aovs.iter().filter(|aov| !aov.is_bounded()).map(|aov| aov.name()).collect();
+    if !clamped.is_empty() && !options.image.format.is_linear() {
+        eprintln!(
+            "warning: {} output clamps the {} AOVs to [0, 1]; use EXR or HDR to keep their values",
+            options.image.format.extension(),
+            clamped.join(", "),
+        );
+    }
+
+    let mut aovs = options.aovs.clone();
+    if options.denoise.is_some() {
+        aovs.extend(denoise::GUIDES.iter().filter(|guide| !options.aovs.contains(guide)));
+    }
 
-    let img = trace(width, height, &camera_pos, &light_pos, &faces);
+    let job = Job {
+        integrator: &options.integrator,
+        aovs: &aovs,
+        mattes: &options.cryptomatte,
+        checkpoint: options.checkpoint.as_ref(),
+    };
+    let render = trace(options, &scene, environment.as_ref(), &job, &mut |render| {
+        save(options, render, &metadata)
+    })?;
+    save(options, &render, &metadata)?;
 
-    let path = format!("{}/{}.bmp", IMAGES_DIR, object_name);
-    img.save(path).expect("Cannot save image");
+    if let Some((path, image)) = &options.heatmap {
+        let framebuffer = heatmap(&render.samples, options.width, options.height);
+        output::write(path, &framebuffer, image)?;
+    }
+
+    for pass in &options.passes {
+        let job = Job {
+            integrator: &pass.integrator,
+            aovs: &[],
+            mattes: &[],
+            checkpoint: None,
+        };
+        let render = trace(options, &scene, environment.as_ref(), &job, &mut |render| {
+            output::write(&pass.output, &render.framebuffer, &pass.image)
+        })?;
+        output::write(&pass.output, &render.framebuffer, &pass.image)?;
+    }
+
+    Ok(())
+}
+
+fn main() {
+    let argv: Vec<String> = env::args().skip(1).collect();
+
+    let options = match cli::parse(&argv) {
+        Ok(Command::Render(options)) => options,
+        Ok(Command::Help) => {
+            print!("{}", cli::USAGE);
+            return;
+        }
+        Err(err) => {
+            eprintln!("error: {}\n\n{}", err, cli::USAGE);
+            process::exit(2);
+        }
+    };
+
+    if let Err(err) = render(&options) {
+        eprintln!("error: {}", err);
+        process::exit(1);
+    }
 }
@@ -0,0 +1,263 @@
+use crate::bsdf::{Bsdf,BlinnPhong,Conductor,Glass,Lambert,Mirror,Mix,Plastic,Principled,RoughDielectric,Scaled};
+use std::borrow::Cow;
+use std::sync::Arc;
+use crate::color::{Color,BLACK,WHITE};
+use crate::texture::{Lookup,Texture};
+
+// below this a lobe is treated as absent
+const MIN_WEIGHT: f64 = 1e-3;
+const DEFAULT_COATING_IOR: f64 = 1.5;
+
+#[derive(Debug,Clone)]
+pub struct Material {
+    pub name: String,
+    pub diffuse: Color,
+    pub specular: Color,
+    pub shininess: f64,
+    pub reflection: Color,
+    pub transmission: Color,
+    pub transparency: f64,
+    pub ior: f64,
+    pub roughness: Option<f64>,
+    pub metallic: Option<f64>,
+    pub sheen: Option<f64>,
+    pub clearcoat: Option<f64>,
+    pub clearcoat_roughness: Option<f64>,
+    pub emission: Color,
+    pub diffuse_map: Option<Arc<dyn Texture>>,
+    pub specular_map: Option<Arc<dyn Texture>>,
+    pub roughness_map: Option<Arc<dyn Texture>>,
+    pub metallic_map: Option<Arc<dyn Texture>>,
+    pub sheen_map: Option<Arc<dyn Texture>>,
+    pub clearcoat_map: Option<Arc<dyn Texture>>,
+    pub opacity_map: Option<Arc<dyn Texture>>,
+    pub emission_map: Option<Arc<dyn Texture>>,
+    // tangent space normals and heights perturbing the shading normal
+    pub normal_map: Option<Arc<dyn Texture>>,
+    pub bump_map: Option<Arc<dyn Texture>>,
+    pub bump_scale: f64,
+}
+
+impl Material {
+    pub fn new(name: &str) -> Self {
+        Material {
+            name: name.to_string(),
+            diffuse: WHITE,
+            specular: BLACK,
+            shininess: 0.,
+            reflection: BLACK,
+            transmission: WHITE,
+            transparency: 0.,
+            ior: 1.,
+            roughness: None,
+            metallic: None,
+            sheen: None,
+            clearcoat: None,
+            clearcoat_roughness: None,
+            emission: BLACK,
+            diffuse_map: None,
+            specular_map: None,
+            roughness_map: None,
+            metallic_map: None,
+            sheen_map: None,
+            clearcoat_map: None,
+            opacity_map: None,
+            emission_map: None,
+            normal_map: None,
+            bump_map: None,
+            bump_scale: 1.,
+        }
+    }
+
+    fn maps(&self) -> [&Option<Arc<dyn Texture>>; 8] {
+        [
+            &self.diffuse_map,
+            &self.specular_map,
+            &self.roughness_map,
+            &self.metallic_map,
+            &self.sheen_map,
+            &self.clearcoat_map,
+            &self.opacity_map,
+            &self.emission_map,
+        ]
+    }
+
+    pub fn is_textured(&self) -> bool {
+        self.maps().iter().any(|map| map.is_some())
+    }
+
+    // the material with its texture maps read at `lookup`; maps that cannot
+    // be evaluated there leave the constant value in place
+    pub fn textured(&self, lookup: &Lookup) -> Cow<'_, Material> {
+        if !self.is_textured() {
+            return Cow::Borrowed(self);
+        }
+
+        let color = |map: &Option<Arc<dyn Texture>>| map.as_ref().and_then(|map| map.color(lookup));
+        let value = |map: &Option<Arc<dyn Texture>>| {
+            map.as_ref().and_then(|map| map.value(lookup)).map(|value| value.clamp(0., 1.))
+        };
+
+        let mut material = self.clone();
+
+        if let Some(texel) = color(&self.diffuse_map) {
+            material.diffuse = self.diffuse * texel;
+        }
+        if let Some(texel) = color(&self.specular_map) {
+            material.specular = self.specular * texel;
+            material.reflection = self.reflection * texel;
+        }
+        if let Some(texel) = value(&self.roughness_map) {
+            material.roughness = Some(texel);
+        }
+        if let Some(texel) = value(&self.metallic_map) {
+            material.metallic = Some(texel);
+        }
+        if let Some(texel) = value(&self.sheen_map) {
+            material.sheen = Some(texel);
+        }
+        if let Some(texel) = value(&self.clearcoat_map) {
+            material.clearcoat = Some(texel);
+        }
+        if let Some(texel) = value(&self.opacity_map) {
+            material.transparency = 1. - (1. - self.transparency) * texel;
+        }
+        if let Some(texel) = color(&self.emission_map) {
+            // a map without Ke is taken at face value
+            let scale = if self.emission == BLACK { WHITE } else { self.emission };
+            material.emission = scale * texel;
+        }
+
+        Cow::Owned(material)
+    }
+
+    // PBR extension statements switch the material to the principled model
+    pub fn principled(&self) -> Option<Principled> {
+        let extended = [self.metallic, self.sheen, self.clearcoat, self.clearcoat_roughness]
+            .iter()
+            .any(Option::is_some)
+            || [&self.metallic_map, &self.sheen_map, &self.clearcoat_map]
+                .iter()
+                .any(|map| map.is_some());
+
+        if !extended {
+            return None;
+        }
+
+        let defaults = Principled::default();
+        let specular = max(self.specular);
+
+        Some(Principled {
+            base_color: self.diffuse,
+            metallic: self.metallic.unwrap_or(defaults.metallic),
+            roughness: self.roughness.unwrap_or(defaults.roughness),
+            specular: if specular > 0. { specular } else { defaults.specular },
+            sheen: self.sheen.unwrap_or(defaults.sheen),
+            clearcoat: self.clearcoat.unwrap_or(defaults.clearcoat),
+            clearcoat_roughness: self.clearcoat_roughness.unwrap_or(defaults.clearcoat_roughness),
+            transmission: self.transparency,
+            ior: if self.ior > 1. { self.ior } else { defaults.ior },
+            ..defaults
+        })
+    }
+
+    pub fn bsdf(&self) -> Box<dyn Bsdf> {
+        if let Some(principled) = self.principled() {
+            return principled.bsdf();
+        }
+
+        let opaque = self.opaque_bsdf();
+
+        if self.transparency <= 0. {
+            return opaque;
+        }
+
+        let ior = self.ior;
+        let dielectric: Box<dyn Bsdf> = match self.roughness {
+            Some(roughness) if roughness > 0. => Box::new(RoughDielectric {
+                ior,
+                roughness,
+                transmission: self.transmission,
+            }),
+            _ => Box::new(Glass { ior, transmission: self.transmission }),
+        };
+
+        if self.transparency >= 1. {
+            return dielectric;
+        }
+
+        Box::new(Mix { first: opaque, second: dielectric, weight: self.transparency })
+    }
+
+    fn coating_ior(&self) -> f64 {
+        if self.ior > 1. { self.ior } else { DEFAULT_COATING_IOR }
+    }
+
+    fn opaque_bsdf(&self) -> Box<dyn Bsdf> {
+        let diffuse = Lambert { albedo: self.diffuse };
+        let diffuse_weight = max(self.diffuse);
+
+        let reflection_weight = max(self.reflection);
+
+        if reflection_weight > MIN_WEIGHT {
+            let roughness = self.roughness.filter(|&roughness| roughness > 0.);
+
+            let metal: Box<dyn Bsdf> = match roughness {
+                // a rough reflective coat over a diffuse base behaves like plastic
+                Some(roughness) if diffuse_weight > MIN_WEIGHT => {
+                    let ior = self.coating_ior();
+                    return Box::new(Plastic {
+                        diffuse: self.diffuse,
+                        coating: self.reflection,
+                        ior,
+                        roughness,
+                    });
+                }
+                Some(roughness) => Box::new(Conductor::from_reflectance(self.reflection, roughness)),
+                None => Box::new(Mirror { reflectance: self.reflection }),
+            };
+            return sum(Box::new(diffuse), diffuse_weight, metal, reflection_weight);
+        }
+
+        if max(self.specular) > MIN_WEIGHT && self.shininess > 0. {
+            let specular = BlinnPhong { specular: self.specular, exponent: self.shininess };
+            return sum(Box::new(diffuse), diffuse_weight, Box::new(specular), max(self.specular));
+        }
+
+        Box::new(diffuse)
+    }
+}
+
+fn max(color: Color) -> f64 {
+    color.r.max(color.g).max(color.b)
+}
+
+// layers two lobes, the first getting only the light the second leaves so
+// together they never reflect more than arrives; each is picked with
+// probability proportional to its weight, and as `Mix` scales by the pick
+// probability the lobes are boosted to compensate
+fn sum(
+    first: Box<dyn Bsdf>,
+    first_weight: f64,
+    second: Box<dyn Bsdf>,
+    second_weight: f64,
+) -> Box<dyn Bsdf> {
+    let remaining = 1. - second_weight.min(1.);
+    let first_weight = first_weight * remaining;
+    if first_weight <= MIN_WEIGHT {
+        return second;
+    }
+
+    let weight = second_weight / (first_weight + second_weight);
+    Box::new(Mix {
+        first: Box::new(Scaled { bsdf: first, factor: remaining / (1. - weight) }),
+        second: Box::new(Scaled { bsdf: second, factor: 1. / weight }),
+        weight,
+    })
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Material::new("default")
+    }
+}
@@ -0,0 +1,20 @@
+use std::io;
+use std::path::Path;
+use bmp::{Image,Pixel};
+use super::{Raster,rgb,to_eight_bits};
+
+pub fn write(path: &Path, raster: &Raster) -> io::Result<()> {
+    let mut img = Image::new(raster.width, raster.height);
+
+    for (x, y) in img.coordinates() {
+        let pixel = &raster.pixels[(y * raster.width + x) as usize];
+        let [r, g, b] = rgb(pixel);
+        img.set_pixel(
+            x,
+            y,
+            Pixel::new(to_eight_bits(r), to_eight_bits(g), to_eight_bits(b))
+        );
+    }
+
+    img.save(path)
+}
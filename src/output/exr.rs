@@ -0,0 +1,433 @@
+use std::io::{self,Write};
+use miniz_oxide::deflate::compress_to_vec_zlib;
+use crate::aov::Layer;
+use crate::framebuffer::Framebuffer;
+use super::{Compression,Depth,Settings};
+
+const MAGIC: [u8; 4] = [0x76, 0x2f, 0x31, 0x01];
+const VERSION: [u8; 4] = [2, 0, 0, 0];
+
+const HALF: i32 = 1;
+const FLOAT: i32 = 2;
+
+const NO_COMPRESSION: u8 = 0;
+const ZIP_COMPRESSION: u8 = 3;
+const ZIP_LINES_PER_BLOCK: u32 = 16;
+
+fn half(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32;
+    let mantissa = bits & 0x007f_ffff;
+
+    if exponent == 0xff {
+        let nan = if mantissa != 0 { 0x0200 } else { 0 };
+        return sign | 0x7c00 | nan;
+    }
+
+    let exponent = exponent - 127 + 15;
+
+    if exponent >= 0x1f {
+        return sign | 0x7c00;
+    }
+
+    if exponent <= 0 {
+        if exponent < -10 {
+            return sign;
+        }
+        let mantissa = mantissa | 0x0080_0000;
+        let shift = (14 - exponent) as u32;
+        let rounded = (mantissa + (1 << (shift - 1))) >> shift;
+        return sign | rounded as u16;
+    }
+
+    let rounded = ((exponent as u32) << 10) + ((mantissa + 0x1000) >> 13);
+    if rounded >= 0x7c00 {
+        return sign | 0x7c00;
+    }
+    sign | rounded as u16
+}
+
+// one named channel, read from a component of a framebuffer: 0 to 2 for
+// red to blue, 3 for alpha
+struct Channel<'f> {
+    name: String,
+    framebuffer: &'f Framebuffer,
+    component: usize,
+    // composite over white when the file has no alpha to carry coverage
+    flatten: bool,
+    pixel_type: i32,
+}
+
+fn attribute(header: &mut Vec<u8>, name: &str, kind: &str, value: &[u8]) {
+    header.extend_from_slice(name.as_bytes());
+    header.push(0);
+    header.extend_from_slice(kind.as_bytes());
+    header.push(0);
+    header.extend_from_slice(&(value.len() as i32).to_le_bytes());
+    header.extend_from_slice(value);
+}
+
+fn header(
+    framebuffer: &Framebuffer,
+    channels: &[Channel],
+    compression: u8,
+    metadata: &[(String, String)],
+) -> Vec<u8> {
+    let mut channel_list: Vec<u8> = vec![];
+    for channel in channels {
+        channel_list.extend_from_slice(channel.name.as_bytes());
+        channel_list.push(0);
+        channel_list.extend_from_slice(&channel.pixel_type.to_le_bytes());
+        channel_list.extend_from_slice(&[0, 0, 0, 0]);
+        channel_list.extend_from_slice(&1i32.to_le_bytes());
+        channel_list.extend_from_slice(&1i32.to_le_bytes());
+    }
+    channel_list.push(0);
+
+    let mut window: Vec<u8> = vec![];
+    for value in [0, 0, framebuffer.width as i32 - 1, framebuffer.height as i32 - 1] {
+        window.extend_from_slice(&value.to_le_bytes());
+    }
+
+    let mut header: Vec<u8> = vec![];
+    header.extend_from_slice(&MAGIC);
+    header.extend_from_slice(&VERSION);
+    attribute(&mut header, "channels", "chlist", &channel_list);
+    attribute(&mut header, "compression", "compression", &[compression]);
+    attribute(&mut header, "dataWindow", "box2i", &window);
+    attribute(&mut header, "displayWindow", "box2i", &window);
+    attribute(&mut header, "lineOrder", "lineOrder", &[0]);
+    attribute(&mut header, "pixelAspectRatio", "float", &1f32.to_le_bytes());
+    attribute(&mut header, "screenWindowCenter", "v2f", &[0; 8]);
+    attribute(&mut header, "screenWindowWidth", "float", &1f32.to_le_bytes());
+    for (name, value) in metadata {
+        attribute(&mut header, name, "string", value.as_bytes());
+    }
+    header.push(0);
+    header
+}
+
+fn scanline(channels: &[Channel], y: u32) -> Vec<u8> {
+    let mut data: Vec<u8> = vec![];
+
+    for channel in channels {
+        let framebuffer = channel.framebuffer;
+        for x in 0..framebuffer.width {
+            let (mut color, alpha) = framebuffer.pixel(x, y);
+            if channel.flatten {
+                color = framebuffer.flattened((y * framebuffer.width + x) as usize);
+            }
+
+            let value = match channel.component {
+                0 => color.r,
+                1 => color.g,
+                2 => color.b,
+                _ => alpha,
+            } as f32;
+
+            if channel.pixel_type == HALF {
+                data.extend_from_slice(&half(value).to_le_bytes());
+            } else {
+                data.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+    }
+
+    data
+}
+
+fn zip(data: &[u8]) -> Vec<u8> {
+    let half = data.len().div_ceil(2);
+    let mut reordered = vec![0u8; data.len()];
+
+    for (index, &byte) in data.iter().enumerate() {
+        if index % 2 == 0 {
+            reordered[index / 2] = byte;
+        } else {
+            reordered[half + index / 2] = byte;
+        }
+    }
+
+    for index in (1..reordered.len()).rev() {
+        let delta = reordered[index] as i32 - reordered[index - 1] as i32 + 128;
+        reordered[index] = delta as u8;
+    }
+
+    compress_to_vec_zlib(&reordered, 6)
+}
+
+// the image as R, G, B and optionally A, followed by every layer channel as
+// `name.Y`, `name.R` and so on; layers are always full floats so IDs and
+// positions survive
+fn channels<'f>(
+    framebuffer: &'f Framebuffer,
+    layers: &'f [Layer],
+    settings: &Settings,
+) -> Vec<Channel<'f>> {
+    let pixel_type = if settings.depth == Depth::ThirtyTwo { FLOAT } else { HALF };
+    let flatten = !settings.alpha;
+    let image = |name: &str, component| Channel {
+        name: name.to_string(),
+        framebuffer,
+        component,
+        flatten: flatten && component < 3,
+        pixel_type,
+    };
+
+    let mut channels = vec![image("R", 0), image("G", 1), image("B", 2)];
+    if settings.alpha {
+        channels.push(image("A", 3));
+    }
+
+    for layer in layers {
+        for (component, suffix) in layer.channels.iter().enumerate() {
+            channels.push(Channel {
+                name: format!("{}.{}", layer.name, suffix),
+                framebuffer: &layer.framebuffer,
+                component,
+                flatten: false,
+                pixel_type: FLOAT,
+            });
+        }
+    }
+
+    // channels must be listed in alphabetical order
+    channels.sort_by(|a, b| a.name.cmp(&b.name));
+    channels
+}
+
+pub fn write<W: Write>(
+    writer: &mut W,
+    framebuffer: &Framebuffer,
+    layers: &[Layer],
+    metadata: &[(String, String)],
+    settings: &Settings,
+) -> io::Result<()> {
+    let channels = channels(framebuffer, layers, settings);
+
+    let (compression, lines_per_block) = match settings.compression {
+        Compression::None => (NO_COMPRESSION, 1),
+        Compression::Zip => (ZIP_COMPRESSION, ZIP_LINES_PER_BLOCK),
+    };
+
+    let mut blocks: Vec<(u32, Vec<u8>)> = vec![];
+
+    for y in (0..framebuffer.height).step_by(lines_per_block as usize) {
+        let last = (y + lines_per_block).min(framebuffer.height);
+        let raw: Vec<u8> = (y..last)
+            .flat_map(|line| scanline(&channels, line))
+            .collect();
+
+        let data = match settings.compression {
+            Compression::None => raw,
+            Compression::Zip => {
+                let packed = zip(&raw);
+                if packed.len() < raw.len() { packed } else { raw }
+            }
+        };
+
+        blocks.push((y, data));
+    }
+
+    let header = header(framebuffer, &channels, compression, metadata);
+    writer.write_all(&header)?;
+
+    let mut offset = (header.len() + blocks.len() * 8) as u64;
+    for (_, data) in &blocks {
+        writer.write_all(&offset.to_le_bytes())?;
+        offset += 8 + data.len() as u64;
+    }
+
+    for (y, data) in &blocks {
+        writer.write_all(&(*y as i32).to_le_bytes())?;
+        writer.write_all(&(data.len() as i32).to_le_bytes())?;
+        writer.write_all(data)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::convert::TryInto;
+    use miniz_oxide::inflate::decompress_to_vec_zlib;
+    use super::*;
+    use crate::color::Color;
+    use crate::output::Format;
+    use crate::tonemap::DisplayTransform;
+
+    fn unhalf(bits: u16) -> f32 {
+        let sign = if bits & 0x8000 != 0 { -1. } else { 1. };
+        let exponent = ((bits >> 10) & 0x1f) as i32;
+        let mantissa = (bits & 0x3ff) as f32;
+        sign * match exponent {
+            0 => mantissa * 2f32.powi(-24),
+            0x1f => f32::INFINITY,
+            _ => (1. + mantissa / 1024.) * 2f32.powi(exponent - 15),
+        }
+    }
+
+    struct Reader<'d> {
+        data: &'d [u8],
+        position: usize,
+    }
+
+    impl Reader<'_> {
+        fn bytes(&mut self, count: usize) -> &[u8] {
+            self.position += count;
+            &self.data[self.position - count..self.position]
+        }
+
+        fn i32(&mut self) -> i32 {
+            i32::from_le_bytes(self.bytes(4).try_into().unwrap())
+        }
+
+        fn name(&mut self) -> String {
+            let length = self.data[self.position..].iter().position(|&byte| byte == 0).unwrap();
+            let name = String::from_utf8(self.bytes(length).to_vec()).unwrap();
+            self.position += 1;
+            name
+        }
+    }
+
+    // undoes `zip`: inflate, then the byte deltas, then the interleaving
+    fn unzip(data: &[u8]) -> Vec<u8> {
+        let mut reordered = decompress_to_vec_zlib(data).unwrap();
+        for index in 1..reordered.len() {
+            reordered[index] = (reordered[index - 1] as i32 + reordered[index] as i32 - 128) as u8;
+        }
+
+        let half = reordered.len().div_ceil(2);
+        (0..reordered.len())
+            .map(|index| match index % 2 {
+                0 => reordered[index / 2],
+                _ => reordered[half + index / 2],
+            })
+            .collect()
+    }
+
+    // every channel of a scanline image this writer produces, by name
+    fn read(data: &[u8]) -> HashMap<String, Vec<f32>> {
+        let mut reader = Reader { data, position: 8 };
+        let mut channels: Vec<(String, i32)> = vec![];
+        let (mut width, mut height, mut compression) = (0, 0, NO_COMPRESSION);
+
+        loop {
+            let name = reader.name();
+            if name.is_empty() {
+                break;
+            }
+            reader.name();
+            let size = reader.i32() as usize;
+            let end = reader.position + size;
+
+            match name.as_str() {
+                "channels" => loop {
+                    let channel = reader.name();
+                    if channel.is_empty() {
+                        break;
+                    }
+                    channels.push((channel, reader.i32()));
+                    reader.bytes(12);
+                },
+                "compression" => compression = reader.bytes(1)[0],
+                "dataWindow" => {
+                    reader.bytes(8);
+                    width = reader.i32() as usize + 1;
+                    height = reader.i32() as usize + 1;
+                }
+                _ => {}
+            }
+            reader.position = end;
+        }
+
+        let lines_per_block = match compression {
+            ZIP_COMPRESSION => ZIP_LINES_PER_BLOCK as usize,
+            _ => 1,
+        };
+        let blocks = height.div_ceil(lines_per_block);
+        let offsets: Vec<usize> = (0..blocks)
+            .map(|_| u64::from_le_bytes(reader.bytes(8).try_into().unwrap()) as usize)
+            .collect();
+        let sample_size = |kind: i32| if kind == HALF { 2 } else { 4 };
+        let line_size: usize = channels.iter().map(|(_, kind)| sample_size(*kind) * width).sum();
+        let mut values: HashMap<String, Vec<f32>> = HashMap::new();
+
+        for offset in offsets {
+            reader.position = offset;
+            let y = reader.i32() as usize;
+            let size = reader.i32() as usize;
+            let raw_size = line_size * lines_per_block.min(height - y);
+            let block = reader.bytes(size);
+            let block = if size < raw_size { unzip(block) } else { block.to_vec() };
+
+            let mut block = Reader { data: &block, position: 0 };
+            while block.position < block.data.len() {
+                for (name, kind) in &channels {
+                    let line = values.entry(name.clone()).or_default();
+                    for _ in 0..width {
+                        line.push(if *kind == HALF {
+                            unhalf(u16::from_le_bytes(block.bytes(2).try_into().unwrap()))
+                        } else {
+                            f32::from_le_bytes(block.bytes(4).try_into().unwrap())
+                        });
+                    }
+                }
+            }
+        }
+
+        values
+    }
+
+    #[test]
+    fn images_and_layers_round_trip() {
+        let (width, height) = (23, 37);
+        let mut framebuffer = Framebuffer::new(width, height);
+        let mut depth = Framebuffer::new(width, height);
+        for y in 0..height {
+            for x in 0..width {
+                let color = Color::new(x as f64 * 0.37, y as f64 * 0.011, 1e-3 * (x + y) as f64);
+                framebuffer.set_pixel(x, y, color, (x % 5) as f64 / 4.);
+                depth.set_pixel(x, y, Color::gray(1000. + (x * y) as f64 / 7.), 1.);
+            }
+        }
+        let layers = [Layer { name: "depth".to_string(), channels: &["Y"], framebuffer: depth }];
+
+        for (depth, compression) in [
+            (Depth::Sixteen, Compression::None),
+            (Depth::Sixteen, Compression::Zip),
+            (Depth::ThirtyTwo, Compression::Zip),
+        ] {
+            let settings = Settings {
+                format: Format::Exr,
+                depth,
+                alpha: true,
+                ascii: false,
+                compression,
+                display: DisplayTransform::default(),
+            };
+            let mut data: Vec<u8> = vec![];
+            write(&mut data, &framebuffer, &layers, &[], &settings).unwrap();
+            let channels = read(&data);
+
+            let mut names: Vec<&str> = channels.keys().map(String::as_str).collect();
+            names.sort_unstable();
+            assert_eq!(names, ["A", "B", "G", "R", "depth.Y"]);
+
+            // half floats keep 11 significant bits
+            let tolerance = if depth == Depth::Sixteen { 1. / 2048. } else { 1e-7 };
+            for (index, color) in framebuffer.pixels.iter().enumerate() {
+                let alpha = framebuffer.alpha[index];
+                for (name, value) in [("R", color.r), ("G", color.g), ("B", color.b), ("A", alpha)] {
+                    let read = channels[name][index] as f64;
+                    let error = (read - value).abs();
+                    assert!(error <= value.abs() * tolerance + 1e-7, "{} {} vs {}", name, read, value);
+                }
+                let depth = layers[0].framebuffer.pixels[index].r;
+                assert_eq!(channels["depth.Y"][index], depth as f32);
+            }
+        }
+    }
+}
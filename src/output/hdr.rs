@@ -0,0 +1,37 @@
+use std::io::{self,Write};
+use crate::framebuffer::Framebuffer;
+
+fn rgbe(r: f64, g: f64, b: f64) -> [u8; 4] {
+    let (r, g, b) = (r.max(0.), g.max(0.), b.max(0.));
+    let max = r.max(g).max(b);
+
+    if max < 1e-32 {
+        return [0; 4];
+    }
+
+    let exponent = max.log2().floor() as i32 + 1;
+    let scale = 256. / 2f64.powi(exponent);
+
+    [
+        (r * scale).min(255.) as u8,
+        (g * scale).min(255.) as u8,
+        (b * scale).min(255.) as u8,
+        (exponent + 128) as u8,
+    ]
+}
+
+pub fn write<W: Write>(writer: &mut W, framebuffer: &Framebuffer) -> io::Result<()> {
+    write!(
+        writer,
+        "#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n",
+        framebuffer.height,
+        framebuffer.width
+    )?;
+
+    for index in 0..framebuffer.pixels.len() {
+        let color = framebuffer.flattened(index);
+        writer.write_all(&rgbe(color.r, color.g, color.b))?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,469 @@
+mod bitmap;
+mod png;
+mod netpbm;
+mod tga;
+mod hdr;
+mod exr;
+
+use std::fs::File;
+use std::io::{BufWriter,Write};
+use std::path::{Path,PathBuf};
+use crate::aov::Layer;
+use crate::framebuffer::Framebuffer;
+use crate::color::WHITE;
+use crate::tonemap::{DisplayTransform,Operator,Transfer};
+
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub enum Format {
+    Bmp,
+    Png,
+    Ppm,
+    Pgm,
+    Tga,
+    Hdr,
+    Exr,
+}
+
+impl Format {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "bmp" => Some(Format::Bmp),
+            "png" => Some(Format::Png),
+            "ppm" => Some(Format::Ppm),
+            "pgm" => Some(Format::Pgm),
+            "tga" => Some(Format::Tga),
+            "hdr" => Some(Format::Hdr),
+            "exr" => Some(Format::Exr),
+            _ => None,
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Format::Bmp => "bmp",
+            Format::Png => "png",
+            Format::Ppm => "ppm",
+            Format::Pgm => "pgm",
+            Format::Tga => "tga",
+            Format::Hdr => "hdr",
+            Format::Exr => "exr",
+        }
+    }
+
+    pub fn default_depth(&self) -> Depth {
+        match self {
+            Format::Exr => Depth::Sixteen,
+            Format::Hdr => Depth::ThirtyTwo,
+            _ => Depth::Eight,
+        }
+    }
+
+    // radiance is stored as is, without any display transform
+    pub fn is_linear(&self) -> bool {
+        matches!(self, Format::Hdr | Format::Exr)
+    }
+
+    pub fn supports_alpha(&self) -> bool {
+        matches!(self, Format::Png | Format::Tga | Format::Exr)
+    }
+
+    pub fn supports_depth(&self, depth: Depth) -> bool {
+        match depth {
+            Depth::Eight => !matches!(self, Format::Hdr | Format::Exr),
+            Depth::Sixteen => matches!(self, Format::Png | Format::Ppm | Format::Pgm | Format::Exr),
+            Depth::ThirtyTwo => matches!(self, Format::Hdr | Format::Exr),
+        }
+    }
+}
+
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub enum Depth {
+    Eight,
+    Sixteen,
+    ThirtyTwo,
+}
+
+impl Depth {
+    pub fn bits(&self) -> u32 {
+        match self {
+            Depth::Eight => 8,
+            Depth::Sixteen => 16,
+            Depth::ThirtyTwo => 32,
+        }
+    }
+}
+
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub enum Compression {
+    None,
+    Zip,
+}
+
+#[derive(Debug,Clone)]
+pub struct Settings {
+    pub format: Format,
+    pub depth: Depth,
+    pub alpha: bool,
+    pub ascii: bool,
+    pub compression: Compression,
+    pub display: DisplayTransform,
+}
+
+impl Settings {
+    pub fn validate(&self) -> Result<(), String> {
+        let name = self.format.extension();
+
+        if self.alpha && !self.format.supports_alpha() {
+            return Err(format!("{} output has no alpha channel", name));
+        }
+
+        if !self.format.supports_depth(self.depth) {
+            return Err(format!(
+                "{} output does not support {}-bit depth",
+                name,
+                self.depth.bits()
+            ));
+        }
+
+        if self.ascii && !matches!(self.format, Format::Ppm | Format::Pgm) {
+            return Err(format!("{} output has no ASCII variant", name));
+        }
+
+        if self.compression != Compression::None && self.format != Format::Exr {
+            return Err(format!("{} output does not support compression", name));
+        }
+
+        Ok(())
+    }
+
+    // AOVs hold data rather than pictures, so they skip the display transform
+    pub fn for_data(&self) -> Settings {
+        Settings {
+            alpha: false,
+            display: DisplayTransform {
+                exposure: 0.,
+                operator: Operator::Clamp,
+                white_point: None,
+                transfer: Transfer::Linear,
+            },
+            ..self.clone()
+        }
+    }
+}
+
+// `image.png` becomes `image.<name>.png`
+pub fn layer_path(path: &Path, name: &str) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let file = match path.extension() {
+        Some(ext) => format!("{}.{}.{}", stem, name, ext.to_string_lossy()),
+        None => format!("{}.{}", stem, name),
+    };
+    path.with_file_name(file)
+}
+
+// 16 bits per channel, display-encoded, straight (not premultiplied) RGBA
+pub struct Raster {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<[u16; 4]>,
+}
+
+impl Raster {
+    pub fn from(framebuffer: &Framebuffer, settings: &Settings) -> Self {
+        let quantize = |value: f64| (value.clamp(0., 1.) * u16::MAX as f64).round() as u16;
+
+        let pixels = (0..framebuffer.pixels.len())
+            .map(|index| {
+                let alpha = framebuffer.alpha[index];
+                let color = settings.display.apply(framebuffer.straight(index));
+
+                let (color, coverage) = if settings.alpha {
+                    (color, alpha)
+                } else {
+                    (color * alpha + WHITE * (1. - alpha), 1.)
+                };
+
+                [
+                    quantize(color.r),
+                    quantize(color.g),
+                    quantize(color.b),
+                    quantize(coverage),
+                ]
+            })
+            .collect();
+
+        Raster {
+            width: framebuffer.width,
+            height: framebuffer.height,
+            pixels,
+        }
+    }
+
+    fn rows(&self) -> std::slice::Chunks<'_, [u16; 4]> {
+        self.pixels.chunks(self.width as usize)
+    }
+}
+
+fn rgb(pixel: &[u16; 4]) -> [u16; 3] {
+    [pixel[0], pixel[1], pixel[2]]
+}
+
+fn to_eight_bits(value: u16) -> u8 {
+    ((value as u32 + 128) / 257) as u8
+}
+
+fn luminance(rgb: &[u16; 3]) -> u16 {
+    let y = 0.2126 * rgb[0] as f64 + 0.7152 * rgb[1] as f64 + 0.0722 * rgb[2] as f64;
+    y.round() as u16
+}
+
+pub fn write(
+    path: &Path,
+    framebuffer: &Framebuffer,
+    settings: &Settings,
+) -> Result<(), String> {
+    write_layers(path, framebuffer, &[], &[], settings)
+}
+
+// AOV layers and string metadata can only travel inside an EXR image
+pub fn write_layers(
+    path: &Path,
+    framebuffer: &Framebuffer,
+    layers: &[Layer],
+    metadata: &[(String, String)],
+    settings: &Settings,
+) -> Result<(), String> {
+    settings.validate()?;
+
+    if !(layers.is_empty() && metadata.is_empty()) && settings.format != Format::Exr {
+        return Err(format!("{} output cannot hold AOV layers", settings.format.extension()));
+    }
+
+    let error = |err: std::io::Error| format!("Cannot save {}: {}", path.display(), err);
+    let raster = || Raster::from(framebuffer, settings);
+
+    if settings.format == Format::Bmp {
+        return bitmap::write(path, &raster()).map_err(error);
+    }
+
+    let file = File::create(path).map_err(error)?;
+    let mut writer = BufWriter::new(file);
+
+    match settings.format {
+        Format::Png => png::write(&mut writer, &raster(), settings),
+        Format::Ppm | Format::Pgm => netpbm::write(&mut writer, &raster(), settings),
+        Format::Tga => tga::write(&mut writer, &raster(), settings),
+        Format::Hdr => hdr::write(&mut writer, framebuffer),
+        Format::Exr => exr::write(&mut writer, framebuffer, layers, metadata, settings),
+        Format::Bmp => unreachable!(),
+    }
+    .and_then(|_| writer.flush())
+    .map_err(error)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use super::*;
+    use crate::color::Color;
+    use crate::texture::read_pixels;
+
+    // smooth ramps in every channel, within [0, max]
+    fn gradient(width: u32, height: u32, max: f64) -> Framebuffer {
+        let mut framebuffer = Framebuffer::new(width, height);
+        for y in 0..height {
+            for x in 0..width {
+                let (u, v) = (x as f64 / (width - 1) as f64, y as f64 / (height - 1) as f64);
+                framebuffer.set_pixel(x, y, Color::new(u, v, (u + v) / 2.) * max, 1.);
+            }
+        }
+        framebuffer
+    }
+
+    fn settings(format: Format, depth: Depth) -> Settings {
+        Settings {
+            format,
+            depth,
+            alpha: false,
+            ascii: false,
+            compression: Compression::None,
+            display: DisplayTransform::default(),
+        }
+    }
+
+    // writes `framebuffer` to a temporary file and reads it back as colors
+    fn round_trip(framebuffer: &Framebuffer, settings: &Settings, name: &str) -> Vec<Color> {
+        let file = format!("trace-{}-{}.{}", name, std::process::id(), settings.format.extension());
+        let path = std::env::temp_dir().join(file);
+        write(&path, framebuffer, settings).unwrap();
+        let pixels = read_pixels(&path, settings.display.transfer == Transfer::Srgb);
+        fs::remove_file(&path).ok();
+
+        let (width, height, pixels) = pixels.unwrap();
+        assert_eq!((width as u32, height as u32), (framebuffer.width, framebuffer.height));
+        pixels
+    }
+
+    // writes `framebuffer` to a temporary file and returns its bytes, for
+    // formats the texture reader cannot load
+    fn written_bytes(framebuffer: &Framebuffer, settings: &Settings, name: &str) -> Vec<u8> {
+        let file = format!("trace-{}-{}.{}", name, std::process::id(), settings.format.extension());
+        let path = std::env::temp_dir().join(file);
+        write(&path, framebuffer, settings).unwrap();
+        let bytes = fs::read(&path);
+        fs::remove_file(&path).ok();
+        bytes.unwrap()
+    }
+
+    // the magic number, size, maximum value and samples of a PPM or PGM file
+    fn read_netpbm(bytes: &[u8]) -> (String, u32, u32, u32, Vec<u32>) {
+        let mut position = 0;
+        let mut header: Vec<String> = vec![];
+        while header.len() < 4 {
+            let start = position;
+            while !bytes[position].is_ascii_whitespace() {
+                position += 1;
+            }
+            header.push(String::from_utf8(bytes[start..position].to_vec()).unwrap());
+            position += 1;
+        }
+
+        let (width, height) = (header[1].parse().unwrap(), header[2].parse().unwrap());
+        let max: u32 = header[3].parse().unwrap();
+        let data = &bytes[position..];
+        let samples = match header[0].as_str() {
+            "P2" | "P3" => std::str::from_utf8(data)
+                .unwrap()
+                .split_whitespace()
+                .map(|value| value.parse().unwrap())
+                .collect(),
+            _ if max > 255 => data
+                .chunks(2)
+                .map(|pair| u16::from_be_bytes([pair[0], pair[1]]) as u32)
+                .collect(),
+            _ => data.iter().map(|&value| value as u32).collect(),
+        };
+
+        (header[0].clone(), width, height, max, samples)
+    }
+
+    fn assert_close(written: &[Color], read: &[Color], tolerance: impl Fn(f64) -> f64) {
+        for (a, b) in written.iter().zip(read) {
+            for (a, b) in [(a.r, b.r), (a.g, b.g), (a.b, b.b)] {
+                assert!((a - b).abs() <= tolerance(a), "wrote {} read {}", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn display_images_round_trip() {
+        let framebuffer = gradient(16, 9, 1.);
+        // half an 8-bit sRGB step, which is widest near white; the texture
+        // reader truncates 16-bit PNGs to 8 bits, so those may be a whole step off
+        for (format, depth, name, tolerance) in [
+            (Format::Png, Depth::Eight, "png8", 0.0045),
+            (Format::Png, Depth::Sixteen, "png16", 0.009),
+            (Format::Bmp, Depth::Eight, "bmp", 0.0045),
+        ] {
+            let read = round_trip(&framebuffer, &settings(format, depth), name);
+            assert_close(&framebuffer.pixels, &read, |_| tolerance);
+        }
+    }
+
+    #[test]
+    fn netpbm_images_read_back() {
+        let framebuffer = gradient(16, 9, 1.);
+        let display = DisplayTransform::default();
+
+        for (format, depth, ascii, magic) in [
+            (Format::Ppm, Depth::Eight, true, "P3"),
+            (Format::Ppm, Depth::Eight, false, "P6"),
+            (Format::Ppm, Depth::Sixteen, true, "P3"),
+            (Format::Ppm, Depth::Sixteen, false, "P6"),
+            (Format::Pgm, Depth::Eight, true, "P2"),
+            (Format::Pgm, Depth::Eight, false, "P5"),
+            (Format::Pgm, Depth::Sixteen, true, "P2"),
+            (Format::Pgm, Depth::Sixteen, false, "P5"),
+        ] {
+            let settings = Settings { ascii, ..settings(format, depth) };
+            let name = format!("{}-{}", magic, depth.bits());
+            let bytes = written_bytes(&framebuffer, &settings, &name);
+            let (read_magic, width, height, max, samples) = read_netpbm(&bytes);
+
+            let expected_max = if depth == Depth::Eight { 255 } else { 65535 };
+            assert_eq!((read_magic.as_str(), width, height, max), (magic, 16, 9, expected_max));
+
+            let channels = if format == Format::Pgm { 1 } else { 3 };
+            assert_eq!(samples.len(), framebuffer.pixels.len() * channels);
+
+            // within a step, as gray is rounded once more than color
+            let step = 1. / max as f64;
+            for (color, read) in framebuffer.pixels.iter().zip(samples.chunks(channels)) {
+                let color = display.apply(*color);
+                let expected = match channels {
+                    1 => vec![0.2126 * color.r + 0.7152 * color.g + 0.0722 * color.b],
+                    _ => vec![color.r, color.g, color.b],
+                };
+                for (expected, read) in expected.iter().zip(read) {
+                    let read = *read as f64 / max as f64;
+                    assert!((expected - read).abs() <= step, "{}: {} vs {}", name, expected, read);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn targa_images_read_back() {
+        // varying coverage, none at all in some pixels
+        let mut framebuffer = gradient(16, 9, 1.);
+        for (index, (color, alpha)) in
+            framebuffer.pixels.iter_mut().zip(&mut framebuffer.alpha).enumerate()
+        {
+            *alpha = (index % 5) as f64 / 4.;
+            *color = *color * *alpha;
+        }
+        let display = DisplayTransform::default();
+
+        for alpha in [false, true] {
+            let settings = Settings { alpha, ..settings(Format::Tga, Depth::Eight) };
+            let bytes = written_bytes(&framebuffer, &settings, &format!("targa-{}", alpha));
+            let (bits, channels) = if alpha { (32, 4) } else { (24, 3) };
+
+            assert_eq!(bytes[2], 2);
+            assert_eq!(u16::from_le_bytes([bytes[12], bytes[13]]), 16);
+            assert_eq!(u16::from_le_bytes([bytes[14], bytes[15]]), 9);
+            assert_eq!(bytes[16], bits);
+            assert_eq!(bytes[17], 0x20 | if alpha { 8 } else { 0 });
+            assert_eq!(bytes.len(), 18 + framebuffer.pixels.len() * channels);
+
+            for (index, read) in bytes[18..].chunks(channels).enumerate() {
+                let color = display.apply(framebuffer.straight(index));
+                let coverage = framebuffer.alpha[index];
+                let expected = match alpha {
+                    true => [color.b, color.g, color.r, coverage],
+                    false => {
+                        let color = color * coverage + WHITE * (1. - coverage);
+                        [color.b, color.g, color.r, 1.]
+                    }
+                };
+                for (expected, read) in expected.iter().zip(read) {
+                    let read = *read as f64 / 255.;
+                    assert!((expected - read).abs() <= 0.5 / 255. + 1e-9, "{} vs {}", expected, read);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn radiance_round_trips() {
+        // the writer stores flat RGBE scanlines, never run-length encoded ones
+        let framebuffer = gradient(32, 9, 8.);
+        let read = round_trip(&framebuffer, &settings(Format::Hdr, Depth::ThirtyTwo), "radiance");
+
+        // RGBE keeps 8 bits of mantissa for the brightest channel
+        for (a, b) in framebuffer.pixels.iter().zip(&read) {
+            let step = a.r.max(a.g).max(a.b) / 128.;
+            assert_close(&[*a], &[*b], |_| step);
+        }
+    }
+}
@@ -0,0 +1,60 @@
+use std::io::{self,Write};
+use super::{Depth,Format,Raster,Settings,luminance,rgb,to_eight_bits};
+
+fn magic(settings: &Settings) -> &'static str {
+    match (settings.format, settings.ascii) {
+        (Format::Pgm, true) => "P2",
+        (Format::Pgm, false) => "P5",
+        (_, true) => "P3",
+        (_, false) => "P6",
+    }
+}
+
+pub fn write<W: Write>(
+    writer: &mut W,
+    raster: &Raster,
+    settings: &Settings,
+) -> io::Result<()> {
+    let eight_bits = settings.depth == Depth::Eight;
+    let max_value = if eight_bits { u8::MAX as u16 } else { u16::MAX };
+
+    write!(
+        writer,
+        "{}\n{} {}\n{}\n",
+        magic(settings),
+        raster.width,
+        raster.height,
+        max_value
+    )?;
+
+    for row in raster.rows() {
+        let mut line: Vec<String> = vec![];
+
+        for pixel in row {
+            let rgb = rgb(pixel);
+            let channels = if settings.format == Format::Pgm {
+                vec![luminance(&rgb)]
+            } else {
+                rgb.to_vec()
+            };
+
+            for value in channels {
+                let value = if eight_bits { to_eight_bits(value) as u16 } else { value };
+
+                if settings.ascii {
+                    line.push(value.to_string());
+                } else if eight_bits {
+                    writer.write_all(&[value as u8])?;
+                } else {
+                    writer.write_all(&value.to_be_bytes())?;
+                }
+            }
+        }
+
+        if settings.ascii {
+            writeln!(writer, "{}", line.join(" "))?;
+        }
+    }
+
+    Ok(())
+}
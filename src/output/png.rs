@@ -0,0 +1,42 @@
+use std::io::{self,Write};
+use ::png::{BitDepth,ColorType,Encoder};
+use super::{Depth,Raster,Settings,rgb,to_eight_bits};
+
+fn encode_error(err: ::png::EncodingError) -> io::Error {
+    io::Error::other(err)
+}
+
+pub fn write<W: Write>(
+    writer: &mut W,
+    raster: &Raster,
+    settings: &Settings,
+) -> io::Result<()> {
+    let mut encoder = Encoder::new(writer, raster.width, raster.height);
+
+    encoder.set_color(if settings.alpha { ColorType::Rgba } else { ColorType::Rgb });
+    let eight_bits = settings.depth == Depth::Eight;
+    encoder.set_depth(if eight_bits { BitDepth::Eight } else { BitDepth::Sixteen });
+
+    let mut data: Vec<u8> = vec![];
+
+    for pixel in &raster.pixels {
+        let channels: Vec<u16> = if settings.alpha {
+            pixel.to_vec()
+        } else {
+            rgb(pixel).to_vec()
+        };
+
+        for value in channels {
+            if eight_bits {
+                data.push(to_eight_bits(value));
+            } else {
+                data.extend_from_slice(&value.to_be_bytes());
+            }
+        }
+    }
+
+    encoder
+        .write_header()
+        .and_then(|mut png| png.write_image_data(&data))
+        .map_err(encode_error)
+}
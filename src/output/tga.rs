@@ -0,0 +1,44 @@
+use std::io::{self,Write};
+use super::{Raster,Settings,rgb,to_eight_bits};
+
+const UNCOMPRESSED_TRUE_COLOR: u8 = 2;
+const TOP_LEFT_ORIGIN: u8 = 0x20;
+
+pub fn write<W: Write>(
+    writer: &mut W,
+    raster: &Raster,
+    settings: &Settings,
+) -> io::Result<()> {
+    if raster.width > u16::MAX as u32 || raster.height > u16::MAX as u32 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "TGA images are limited to 65535x65535 pixels",
+        ));
+    }
+
+    let (bits_per_pixel, alpha_bits) = if settings.alpha { (32, 8) } else { (24, 0) };
+
+    let mut header = [0u8; 18];
+    header[2] = UNCOMPRESSED_TRUE_COLOR;
+    header[12..14].copy_from_slice(&(raster.width as u16).to_le_bytes());
+    header[14..16].copy_from_slice(&(raster.height as u16).to_le_bytes());
+    header[16] = bits_per_pixel;
+    header[17] = TOP_LEFT_ORIGIN | alpha_bits;
+    writer.write_all(&header)?;
+
+    for pixel in &raster.pixels {
+        if settings.alpha {
+            writer.write_all(&[
+                to_eight_bits(pixel[2]),
+                to_eight_bits(pixel[1]),
+                to_eight_bits(pixel[0]),
+                to_eight_bits(pixel[3]),
+            ])?;
+        } else {
+            let [r, g, b] = rgb(pixel);
+            writer.write_all(&[to_eight_bits(b), to_eight_bits(g), to_eight_bits(r)])?;
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,23 @@
+use crate::geometry::Trigon;
+use crate::material::Material;
+
+pub struct Scene<'a> {
+    pub faces: Vec<Trigon<'a>>,
+    pub materials: Vec<Material>,
+    // names from `o` and `g` lines, `default` for faces outside any
+    pub objects: Vec<String>,
+}
+
+impl<'a> Scene<'a> {
+    // numbers the faces, so a face found through the tree knows its index
+    pub fn new(mut faces: Vec<Trigon<'a>>, materials: Vec<Material>, objects: Vec<String>) -> Self {
+        for (index, face) in faces.iter_mut().enumerate() {
+            face.index = index;
+        }
+        Scene { faces, materials, objects }
+    }
+
+    pub fn material(&self, trigon: &Trigon) -> &Material {
+        &self.materials[trigon.material]
+    }
+}
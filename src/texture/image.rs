@@ -0,0 +1,208 @@
+use std::fs::{self,File};
+use std::io::{BufReader,Read};
+use std::path::Path;
+use std::sync::Arc;
+use crate::color::Color;
+use crate::tonemap::Transfer;
+use super::mipmap::MipMap;
+use super::{Lookup,Texture,Wrap};
+
+const PNG_SIGNATURE: [u8; 8] = [137, 80, 78, 71, 13, 10, 26, 10];
+
+// 8-bit texels, decoded to linear values when the image holds colors
+fn decode(value: u8, srgb: bool) -> f64 {
+    let value = value as f64 / 255.;
+    if srgb { Transfer::Srgb.decode(value) } else { value }
+}
+
+// width, height and texels in rows from the top
+type Pixels = (usize, usize, Vec<Color>);
+
+fn read_bmp(path: &Path, srgb: bool) -> Result<Pixels, String> {
+    let image = bmp::open(path).map_err(|err| err.to_string())?;
+    let (width, height) = (image.get_width(), image.get_height());
+
+    let texels = (0..height)
+        .flat_map(|y| (0..width).map(move |x| (x, y)))
+        .map(|(x, y)| {
+            let pixel = image.get_pixel(x, y);
+            Color::new(decode(pixel.r, srgb), decode(pixel.g, srgb), decode(pixel.b, srgb))
+        })
+        .collect();
+
+    Ok((width as usize, height as usize, texels))
+}
+
+fn read_png(path: &Path, srgb: bool) -> Result<Pixels, String> {
+    let file = File::open(path).map_err(|err| err.to_string())?;
+    let mut decoder = png::Decoder::new(BufReader::new(file));
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+
+    let mut reader = decoder.read_info().map_err(|err| err.to_string())?;
+    let mut data = vec![0; reader.output_buffer_size()];
+    let frame = reader.next_frame(&mut data).map_err(|err| err.to_string())?;
+
+    let channels = frame.color_type.samples();
+    let texels = data[..frame.buffer_size()]
+        .chunks(channels)
+        .map(|texel| match channels {
+            1 | 2 => Color::gray(decode(texel[0], srgb)),
+            _ => Color::new(decode(texel[0], srgb), decode(texel[1], srgb), decode(texel[2], srgb)),
+        })
+        .collect();
+
+    Ok((frame.width as usize, frame.height as usize, texels))
+}
+
+fn rgbe(texel: &[u8]) -> Color {
+    if texel[3] == 0 {
+        return Color::gray(0.);
+    }
+    let scale = 2f64.powi(texel[3] as i32 - 136);
+    Color::new(texel[0] as f64 * scale, texel[1] as f64 * scale, texel[2] as f64 * scale)
+}
+
+// one scanline of adaptive run-length encoded RGBE, channel by channel
+fn read_hdr_runs(data: &[u8], position: &mut usize, width: usize) -> Result<Vec<u8>, String> {
+    let mut line = vec![0; width * 4];
+
+    for channel in 0..4 {
+        let mut x = 0;
+        while x < width {
+            let count = *data.get(*position).ok_or("truncated scanline")? as usize;
+            *position += 1;
+
+            if count > 128 {
+                let count = count - 128;
+                let value = *data.get(*position).ok_or("truncated scanline")?;
+                *position += 1;
+                if x + count > width {
+                    return Err("run overflows scanline".to_string());
+                }
+                for offset in 0..count {
+                    line[(x + offset) * 4 + channel] = value;
+                }
+                x += count;
+            } else {
+                if count == 0 || x + count > width {
+                    return Err("bad scanline run".to_string());
+                }
+                let values = data.get(*position..*position + count).ok_or("truncated scanline")?;
+                *position += count;
+                for (offset, value) in values.iter().enumerate() {
+                    line[(x + offset) * 4 + channel] = *value;
+                }
+                x += count;
+            }
+        }
+    }
+
+    Ok(line)
+}
+
+// Radiance RGBE, flat or run-length encoded scanlines, always linear
+fn read_hdr(path: &Path) -> Result<Pixels, String> {
+    let data = fs::read(path).map_err(|err| err.to_string())?;
+    let mut position = 0;
+    let mut next_line = || {
+        let end = data[position..].iter().position(|byte| *byte == b'\n')?;
+        let line = String::from_utf8_lossy(&data[position..position + end]).into_owned();
+        position += end + 1;
+        Some(line)
+    };
+
+    loop {
+        let line = next_line().ok_or("truncated header")?;
+        if line.starts_with("FORMAT=") && line.trim() != "FORMAT=32-bit_rle_rgbe" {
+            return Err(format!("unsupported {}", line.trim()));
+        }
+        if line.trim().is_empty() {
+            break;
+        }
+    }
+
+    let resolution = next_line().ok_or("missing resolution")?;
+    let (height, width) = match resolution.split_whitespace().collect::<Vec<_>>()[..] {
+        ["-Y", height, "+X", width] => (
+            height.parse::<usize>().map_err(|_| "bad resolution")?,
+            width.parse::<usize>().map_err(|_| "bad resolution")?,
+        ),
+        _ => return Err(format!("unsupported orientation '{}'", resolution.trim())),
+    };
+
+    let mut texels = Vec::with_capacity(width * height);
+
+    for _ in 0..height {
+        let encoded = (8..0x8000).contains(&width)
+            && data.get(position..position + 4).is_some_and(|head| {
+                head[0] == 2 && head[1] == 2 && ((head[2] as usize) << 8 | head[3] as usize) == width
+            });
+
+        let line = if encoded {
+            position += 4;
+            read_hdr_runs(&data, &mut position, width)?
+        } else {
+            let line = data.get(position..position + width * 4).ok_or("truncated scanline")?;
+            position += width * 4;
+            line.to_vec()
+        };
+
+        texels.extend(line.chunks(4).map(rgbe));
+    }
+
+    Ok((width, height, texels))
+}
+
+// picks the decoder from the file signature, not the extension
+pub fn read_pixels(path: &Path, srgb: bool) -> Result<Pixels, String> {
+    let mut signature = [0; 8];
+    File::open(path)
+        .and_then(|mut file| file.read_exact(&mut signature))
+        .map_err(|err| format!("Cannot read {}: {}", path.display(), err))?;
+
+    let image = if signature == PNG_SIGNATURE {
+        read_png(path, srgb)
+    } else if signature.starts_with(b"BM") {
+        read_bmp(path, srgb)
+    } else if signature.starts_with(b"#?") {
+        read_hdr(path)
+    } else {
+        Err("unsupported image format".to_string())
+    };
+
+    image.map_err(|err| format!("Cannot load image {}: {}", path.display(), err))
+}
+
+pub fn read_image(path: &Path, srgb: bool) -> Result<MipMap, String> {
+    let (width, height, texels) = read_pixels(path, srgb)?;
+    Ok(MipMap::new(width, height, texels))
+}
+
+#[derive(Clone)]
+pub struct ImageTexture {
+    pub image: Arc<MipMap>,
+    pub wrap: Wrap,
+    pub scale: (f64, f64),
+    pub offset: (f64, f64),
+}
+
+impl std::fmt::Debug for ImageTexture {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "ImageTexture({}x{}, {:?})", self.image.width(), self.image.height(), self.wrap)
+    }
+}
+
+impl Texture for ImageTexture {
+    fn color(&self, lookup: &Lookup) -> Option<Color> {
+        let (u, v) = lookup.uv?;
+        let s = u * self.scale.0 + self.offset.0;
+        // OBJ texture coordinates grow upwards, image rows downwards
+        let t = 1. - (v * self.scale.1 + self.offset.1);
+
+        let footprint = lookup
+            .footprint
+            .map(|(du, dv)| (du * self.scale.0, -dv * self.scale.1));
+
+        Some(self.image.sample(s, t, &footprint, lookup.filter, self.wrap))
+    }
+}
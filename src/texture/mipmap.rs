@@ -0,0 +1,191 @@
+use crate::color::{Color,BLACK};
+use super::{Filter,Wrap};
+
+const MAX_ANISOTROPY: f64 = 16.;
+
+struct Level {
+    width: usize,
+    height: usize,
+    texels: Vec<Color>,
+}
+
+impl Level {
+    fn texel(&self, x: i64, y: i64, wrap: Wrap) -> Color {
+        let x = wrap.apply(x, self.width);
+        let y = wrap.apply(y, self.height);
+        self.texels[y * self.width + x]
+    }
+
+    // box-filtered half resolution copy
+    fn downsample(&self) -> Level {
+        let width = (self.width / 2).max(1);
+        let height = (self.height / 2).max(1);
+        let mut texels = Vec::with_capacity(width * height);
+
+        for y in 0..height {
+            for x in 0..width {
+                let mut total = BLACK;
+                for (row, row_weight) in taps(self.height, y) {
+                    for (column, column_weight) in taps(self.width, x) {
+                        let weight = row_weight * column_weight;
+                        total += self.texel(column, row, Wrap::Clamp) * weight;
+                    }
+                }
+                texels.push(total);
+            }
+        }
+
+        Level { width, height, texels }
+    }
+}
+
+// source texels and weights behind texel `index` of the next level; an odd
+// size spreads its extra texel over three taps, so every texel counts equally
+// and none is dropped
+fn taps(size: usize, index: usize) -> Vec<(i64, f64)> {
+    let first = 2 * index as i64;
+    if size == 1 {
+        return vec![(0, 1.)];
+    }
+    if size.is_multiple_of(2) {
+        return vec![(first, 0.5), (first + 1, 0.5)];
+    }
+
+    let (half, size, index) = ((size / 2) as f64, size as f64, index as f64);
+    vec![
+        (first, (half - index) / size),
+        (first + 1, half / size),
+        (first + 2, (index + 1.) / size),
+    ]
+}
+
+// image pyramid down to a single texel; texture space runs from (0, 0)
+// at the top left to (1, 1) at the bottom right
+pub struct MipMap {
+    levels: Vec<Level>,
+}
+
+impl MipMap {
+    pub fn new(width: usize, height: usize, texels: Vec<Color>) -> Self {
+        let mut levels = vec![Level { width, height, texels }];
+
+        while let Some(last) = levels.last().filter(|level| level.width > 1 || level.height > 1) {
+            let next = last.downsample();
+            levels.push(next);
+        }
+
+        MipMap { levels }
+    }
+
+    pub fn width(&self) -> usize {
+        self.levels[0].width
+    }
+
+    pub fn height(&self) -> usize {
+        self.levels[0].height
+    }
+
+    fn bilinear(&self, level: usize, s: f64, t: f64, wrap: Wrap) -> Color {
+        let level = &self.levels[level.min(self.levels.len() - 1)];
+        let x = s * level.width as f64 - 0.5;
+        let y = t * level.height as f64 - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as i64, y0 as i64);
+
+        level.texel(x0, y0, wrap) * ((1. - fx) * (1. - fy))
+            + level.texel(x0 + 1, y0, wrap) * (fx * (1. - fy))
+            + level.texel(x0, y0 + 1, wrap) * ((1. - fx) * fy)
+            + level.texel(x0 + 1, y0 + 1, wrap) * (fx * fy)
+    }
+
+    // `width` is the filter footprint in texels of the full resolution image
+    fn trilinear(&self, s: f64, t: f64, width: f64, wrap: Wrap) -> Color {
+        let last = (self.levels.len() - 1) as f64;
+        let lod = width.max(1.).log2().min(last);
+        let level = lod.floor();
+        let fraction = lod - level;
+        let level = level as usize;
+
+        if fraction <= 0. {
+            return self.bilinear(level, s, t, wrap);
+        }
+
+        self.bilinear(level, s, t, wrap) * (1. - fraction)
+            + self.bilinear(level + 1, s, t, wrap) * fraction
+    }
+
+    // several trilinear probes along the major axis of the footprint,
+    // each filtered to the width of the minor axis
+    fn anisotropic(&self, s: f64, t: f64, footprint: &[(f64, f64); 2], wrap: Wrap) -> Color {
+        let (major, minor) = if self.texels(&footprint[0]) >= self.texels(&footprint[1]) {
+            (footprint[0], footprint[1])
+        } else {
+            (footprint[1], footprint[0])
+        };
+
+        let major_length = self.texels(&major);
+        let minor_length = self.texels(&minor).max(major_length / MAX_ANISOTROPY);
+
+        if minor_length <= 0. {
+            return self.bilinear(0, s, t, wrap);
+        }
+
+        let count = (major_length / minor_length).ceil().clamp(1., MAX_ANISOTROPY) as usize;
+        let mut total = BLACK;
+
+        for index in 0..count {
+            let offset = (index as f64 + 0.5) / count as f64 - 0.5;
+            total += self.trilinear(s + major.0 * offset, t + major.1 * offset, minor_length, wrap);
+        }
+
+        total * (1. / count as f64)
+    }
+
+    // length of a texture space vector measured in full resolution texels
+    fn texels(&self, axis: &(f64, f64)) -> f64 {
+        let x = axis.0 * self.width() as f64;
+        let y = axis.1 * self.height() as f64;
+        (x * x + y * y).sqrt()
+    }
+
+    pub fn sample(&self, s: f64, t: f64, footprint: &[(f64, f64); 2], filter: Filter, wrap: Wrap) -> Color {
+        match filter {
+            Filter::Bilinear => self.bilinear(0, s, t, wrap),
+            Filter::Trilinear => {
+                let width = self.texels(&footprint[0]).max(self.texels(&footprint[1]));
+                self.trilinear(s, t, width, wrap)
+            }
+            Filter::Anisotropic => self.anisotropic(s, t, footprint, wrap),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn odd_sizes_keep_every_texel() {
+        let (width, height) = (7, 5);
+        // the last row and column are the only bright ones
+        let texels: Vec<Color> = (0..width * height)
+            .map(|index| match (index % width, index / width) {
+                (6, _) | (_, 4) => Color::gray(10.),
+                _ => Color::gray(1.),
+            })
+            .collect();
+        let mean = texels.iter().map(|texel| texel.r).sum::<f64>() / texels.len() as f64;
+        let mipmap = MipMap::new(width, height, texels);
+
+        let sizes: Vec<(usize, usize)> =
+            mipmap.levels.iter().map(|level| (level.width, level.height)).collect();
+        assert_eq!(sizes, [(7, 5), (3, 2), (1, 1)]);
+
+        for level in &mipmap.levels {
+            let total: f64 = level.texels.iter().map(|texel| texel.r).sum();
+            assert!((total / level.texels.len() as f64 - mean).abs() < 1e-9);
+        }
+        assert!(mipmap.levels[1].texels[5].r > mipmap.levels[1].texels[0].r);
+    }
+}
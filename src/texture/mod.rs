@@ -0,0 +1,81 @@
+mod mipmap;
+mod image;
+mod noise;
+mod procedural;
+
+use std::fmt;
+use crate::color::Color;
+use crate::geometry::Point;
+
+pub use image::{ImageTexture,read_image,read_pixels};
+pub use mipmap::MipMap;
+pub use procedural::{Pattern,Procedural,Space};
+
+pub trait Texture: fmt::Debug + Send + Sync {
+    // `None` when the surface lacks what the texture needs, e.g. UVs
+    fn color(&self, lookup: &Lookup) -> Option<Color>;
+
+    // scalar maps such as roughness read the texture as gray
+    fn value(&self, lookup: &Lookup) -> Option<f64> {
+        self.color(lookup).map(|color| color.luminance())
+    }
+}
+
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub enum Wrap {
+    Repeat,
+    Clamp,
+    Mirror,
+}
+
+impl Wrap {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "repeat" => Some(Wrap::Repeat),
+            "clamp" => Some(Wrap::Clamp),
+            "mirror" => Some(Wrap::Mirror),
+            _ => None,
+        }
+    }
+
+    fn apply(&self, index: i64, size: usize) -> usize {
+        let size = size as i64;
+        let index = match self {
+            Wrap::Repeat => index.rem_euclid(size),
+            Wrap::Clamp => index.clamp(0, size - 1),
+            Wrap::Mirror => {
+                let index = index.rem_euclid(2 * size);
+                if index >= size { 2 * size - 1 - index } else { index }
+            }
+        };
+        index as usize
+    }
+}
+
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub enum Filter {
+    Bilinear,
+    Trilinear,
+    Anisotropic,
+}
+
+impl Filter {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "bilinear" => Some(Filter::Bilinear),
+            "trilinear" => Some(Filter::Trilinear),
+            "anisotropic" => Some(Filter::Anisotropic),
+            _ => None,
+        }
+    }
+}
+
+// where a texture is read: the surface point, its texture coordinates and
+// the two axes of the pixel footprint ellipse expressed in texture space
+#[derive(Debug,Clone)]
+pub struct Lookup {
+    pub point: Point,
+    pub uv: Option<(f64, f64)>,
+    pub footprint: [(f64, f64); 2],
+    pub filter: Filter,
+}
@@ -0,0 +1,186 @@
+// gradient and cellular noise after Perlin (2002), Gustavson (2005) and
+// Worley (1996); all functions are deterministic in their input
+
+// Ken Perlin's reference permutation
+const PERMUTATION: [u8; 256] = [
+    151, 160, 137, 91, 90, 15, 131, 13, 201, 95, 96, 53, 194, 233, 7, 225, 140, 36, 103, 30,
+    69, 142, 8, 99, 37, 240, 21, 10, 23, 190, 6, 148, 247, 120, 234, 75, 0, 26, 197, 62, 94,
+    252, 219, 203, 117, 35, 11, 32, 57, 177, 33, 88, 237, 149, 56, 87, 174, 20, 125, 136, 171,
+    168, 68, 175, 74, 165, 71, 134, 139, 48, 27, 166, 77, 146, 158, 231, 83, 111, 229, 122, 60,
+    211, 133, 230, 220, 105, 92, 41, 55, 46, 245, 40, 244, 102, 143, 54, 65, 25, 63, 161, 1,
+    216, 80, 73, 209, 76, 132, 187, 208, 89, 18, 169, 200, 196, 135, 130, 116, 188, 159, 86,
+    164, 100, 109, 198, 173, 186, 3, 64, 52, 217, 226, 250, 124, 123, 5, 202, 38, 147, 118,
+    126, 255, 82, 85, 212, 207, 206, 59, 227, 47, 16, 58, 17, 182, 189, 28, 42, 223, 183, 170,
+    213, 119, 248, 152, 2, 44, 154, 163, 70, 221, 153, 101, 155, 167, 43, 172, 9, 129, 22, 39,
+    253, 19, 98, 108, 110, 79, 113, 224, 232, 178, 185, 112, 104, 218, 246, 97, 228, 251, 34,
+    242, 193, 238, 210, 144, 12, 191, 179, 162, 241, 81, 51, 145, 235, 249, 14, 239, 107, 49,
+    192, 214, 31, 181, 199, 106, 157, 184, 84, 204, 176, 115, 121, 50, 45, 127, 4, 150, 254,
+    138, 236, 205, 93, 222, 114, 67, 29, 24, 72, 243, 141, 128, 195, 78, 66, 215, 61, 156, 180,
+];
+
+// the twelve cube edge midpoints used as gradients
+const GRADIENTS: [[f64; 3]; 12] = [
+    [1., 1., 0.], [-1., 1., 0.], [1., -1., 0.], [-1., -1., 0.],
+    [1., 0., 1.], [-1., 0., 1.], [1., 0., -1.], [-1., 0., -1.],
+    [0., 1., 1.], [0., -1., 1.], [0., 1., -1.], [0., -1., -1.],
+];
+
+fn hash(x: i64, y: i64, z: i64) -> usize {
+    let p = |i: i64| PERMUTATION[(i & 255) as usize] as i64;
+    p(x + p(y + p(z))) as usize
+}
+
+fn gradient(hash: usize, x: f64, y: f64, z: f64) -> f64 {
+    let g = GRADIENTS[hash % 12];
+    g[0] * x + g[1] * y + g[2] * z
+}
+
+fn fade(t: f64) -> f64 {
+    t * t * t * (t * (t * 6. - 15.) + 10.)
+}
+
+fn lerp(t: f64, a: f64, b: f64) -> f64 {
+    a + t * (b - a)
+}
+
+// improved Perlin noise in roughly [-1, 1]
+pub fn perlin(x: f64, y: f64, z: f64) -> f64 {
+    let (xi, yi, zi) = (x.floor() as i64, y.floor() as i64, z.floor() as i64);
+    let (x, y, z) = (x - x.floor(), y - y.floor(), z - z.floor());
+    let (u, v, w) = (fade(x), fade(y), fade(z));
+
+    let corner = |dx: i64, dy: i64, dz: i64| {
+        gradient(hash(xi + dx, yi + dy, zi + dz), x - dx as f64, y - dy as f64, z - dz as f64)
+    };
+
+    lerp(
+        w,
+        lerp(
+            v,
+            lerp(u, corner(0, 0, 0), corner(1, 0, 0)),
+            lerp(u, corner(0, 1, 0), corner(1, 1, 0)),
+        ),
+        lerp(
+            v,
+            lerp(u, corner(0, 0, 1), corner(1, 0, 1)),
+            lerp(u, corner(0, 1, 1), corner(1, 1, 1)),
+        ),
+    )
+}
+
+// 3D simplex noise in roughly [-1, 1]
+pub fn simplex(x: f64, y: f64, z: f64) -> f64 {
+    const SKEW: f64 = 1. / 3.;
+    const UNSKEW: f64 = 1. / 6.;
+
+    let s = (x + y + z) * SKEW;
+    let (i, j, k) = ((x + s).floor(), (y + s).floor(), (z + s).floor());
+    let t = (i + j + k) * UNSKEW;
+    let x0 = [x - (i - t), y - (j - t), z - (k - t)];
+
+    // walk the simplex along the axes in decreasing order of x0
+    let mut order = [0, 1, 2];
+    order.sort_by(|a, b| x0[*b].total_cmp(&x0[*a]));
+
+    let mut offset = [0i64; 3];
+    let mut total = 0.;
+
+    for corner in 0..4 {
+        if corner > 0 {
+            offset[order[corner - 1]] += 1;
+        }
+
+        let shift = corner as f64 * UNSKEW;
+        let d = [
+            x0[0] - offset[0] as f64 + shift,
+            x0[1] - offset[1] as f64 + shift,
+            x0[2] - offset[2] as f64 + shift,
+        ];
+        let falloff = 0.6 - d[0] * d[0] - d[1] * d[1] - d[2] * d[2];
+
+        if falloff > 0. {
+            let hash = hash(i as i64 + offset[0], j as i64 + offset[1], k as i64 + offset[2]);
+            total += falloff.powi(4) * gradient(hash, d[0], d[1], d[2]);
+        }
+    }
+
+    32. * total
+}
+
+// fractional Brownian motion: octaves of Perlin noise at doubling frequency
+pub fn fbm(x: f64, y: f64, z: f64, octaves: u32) -> f64 {
+    octaves_sum(x, y, z, octaves, perlin)
+}
+
+// like fBm but summing absolute values, giving sharp creases
+pub fn turbulence(x: f64, y: f64, z: f64, octaves: u32) -> f64 {
+    octaves_sum(x, y, z, octaves, |x, y, z| perlin(x, y, z).abs())
+}
+
+fn octaves_sum<F: Fn(f64, f64, f64) -> f64>(x: f64, y: f64, z: f64, octaves: u32, noise: F) -> f64 {
+    let mut total = 0.;
+    let mut frequency = 1.;
+    let mut amplitude = 1.;
+    let mut norm = 0.;
+
+    for _ in 0..octaves.max(1) {
+        total += noise(x * frequency, y * frequency, z * frequency) * amplitude;
+        norm += amplitude;
+        frequency *= 2.;
+        amplitude *= 0.5;
+    }
+
+    total / norm
+}
+
+// distance to the nearest of one random feature point per unit cell, in
+// [0, 1] as a fraction of the farthest it can be, the diagonal of the cell
+// holding the point
+pub fn worley(x: f64, y: f64, z: f64) -> f64 {
+    let (xi, yi, zi) = (x.floor() as i64, y.floor() as i64, z.floor() as i64);
+    let mut nearest = f64::INFINITY;
+
+    for dz in -1..=1 {
+        for dy in -1..=1 {
+            for dx in -1..=1 {
+                let (cx, cy, cz) = (xi + dx, yi + dy, zi + dz);
+                let feature = [
+                    cx as f64 + unit(cx, cy, cz, 0),
+                    cy as f64 + unit(cx, cy, cz, 1),
+                    cz as f64 + unit(cx, cy, cz, 2),
+                ];
+                let distance = ((feature[0] - x).powi(2)
+                    + (feature[1] - y).powi(2)
+                    + (feature[2] - z).powi(2))
+                .sqrt();
+                nearest = nearest.min(distance);
+            }
+        }
+    }
+
+    nearest / 3f64.sqrt()
+}
+
+// hashed value in [0, 1) for a cell and channel
+fn unit(x: i64, y: i64, z: i64, channel: i64) -> f64 {
+    let first = hash(x, y, z);
+    let second = hash(first as i64 + channel * 71, z + channel, x);
+    (first * 256 + second) as f64 / 65536.
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn worley_stays_in_unit_range() {
+        let mut farthest: f64 = 0.;
+        for index in 0..20_000 {
+            let t = index as f64 * 0.013;
+            let value = worley(t * 1.7 - 40., (t * 3.1).sin() * 9., t * 0.37);
+            assert!((0. ..=1.).contains(&value), "worley {}", value);
+            farthest = farthest.max(value);
+        }
+        assert!(farthest > 0.5);
+    }
+}
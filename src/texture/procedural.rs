@@ -0,0 +1,119 @@
+use std::f64::consts::PI;
+use crate::color::Color;
+use super::noise::{fbm,perlin,simplex,turbulence,worley};
+use super::{Lookup,Texture};
+
+const DEFAULT_OCTAVES: u32 = 6;
+
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub enum Pattern {
+    Checker,
+    Perlin,
+    Simplex,
+    Fbm,
+    Turbulence,
+    Marble,
+    Wood,
+    Worley,
+}
+
+impl Pattern {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "checker" | "checkerboard" => Some(Pattern::Checker),
+            "perlin" => Some(Pattern::Perlin),
+            "simplex" => Some(Pattern::Simplex),
+            "fbm" => Some(Pattern::Fbm),
+            "turbulence" => Some(Pattern::Turbulence),
+            "marble" => Some(Pattern::Marble),
+            "wood" => Some(Pattern::Wood),
+            "worley" | "cellular" => Some(Pattern::Worley),
+            _ => None,
+        }
+    }
+}
+
+// where a procedural texture is evaluated
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub enum Space {
+    Object,
+    Uv,
+}
+
+impl Space {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "object" => Some(Space::Object),
+            "uv" => Some(Space::Uv),
+            _ => None,
+        }
+    }
+}
+
+// pattern value in [0, 1] blending between two colors
+#[derive(Debug,Clone)]
+pub struct Procedural {
+    pub pattern: Pattern,
+    pub space: Space,
+    pub scale: [f64; 3],
+    pub offset: [f64; 3],
+    pub colors: [Color; 2],
+    pub octaves: u32,
+}
+
+impl Procedural {
+    pub fn new(pattern: Pattern) -> Self {
+        Procedural {
+            pattern,
+            space: Space::Object,
+            scale: [1.; 3],
+            offset: [0.; 3],
+            colors: [Color::gray(0.), Color::gray(1.)],
+            octaves: DEFAULT_OCTAVES,
+        }
+    }
+
+    pub fn value_at(&self, x: f64, y: f64, z: f64) -> f64 {
+        let octaves = self.octaves;
+
+        let value = match self.pattern {
+            Pattern::Checker => {
+                let parity = x.floor() + y.floor() + z.floor();
+                parity.rem_euclid(2.)
+            }
+            Pattern::Perlin => 0.5 + 0.5 * perlin(x, y, z),
+            Pattern::Simplex => 0.5 + 0.5 * simplex(x, y, z),
+            Pattern::Fbm => 0.5 + 0.5 * fbm(x, y, z, octaves),
+            Pattern::Turbulence => turbulence(x, y, z, octaves),
+            // veins along x displaced by turbulence
+            Pattern::Marble => 0.5 + 0.5 * ((x + 4. * turbulence(x, y, z, octaves)) * PI).sin(),
+            // rings around the z axis with noisy radii
+            Pattern::Wood => {
+                let radius = (x * x + y * y).sqrt() + 0.25 * fbm(x, y, z * 0.25, octaves);
+                let ring = (radius * 4.).fract();
+                ring * ring
+            }
+            Pattern::Worley => worley(x, y, z),
+        };
+
+        value.clamp(0., 1.)
+    }
+}
+
+impl Texture for Procedural {
+    fn color(&self, lookup: &Lookup) -> Option<Color> {
+        let position = match self.space {
+            Space::Object => [lookup.point.x, lookup.point.y, lookup.point.z],
+            Space::Uv => {
+                let (u, v) = lookup.uv?;
+                [u, v, 0.]
+            }
+        };
+
+        let coordinate = |axis: usize| position[axis] * self.scale[axis] + self.offset[axis];
+        let t = self.value_at(coordinate(0), coordinate(1), coordinate(2));
+        let [first, second] = self.colors;
+
+        Some(first * (1. - t) + second * t)
+    }
+}
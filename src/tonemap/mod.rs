@@ -0,0 +1,193 @@
+use crate::color::Color;
+
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub enum Operator {
+    Clamp,
+    Reinhard,
+    ExtendedReinhard,
+    Aces,
+    Hable,
+}
+
+impl Operator {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "none" | "clamp" => Some(Operator::Clamp),
+            "reinhard" => Some(Operator::Reinhard),
+            "reinhard-extended" => Some(Operator::ExtendedReinhard),
+            "aces" => Some(Operator::Aces),
+            "hable" | "filmic" => Some(Operator::Hable),
+            _ => None,
+        }
+    }
+
+    fn default_white_point(&self) -> f64 {
+        match self {
+            Operator::Hable => 5.6,
+            _ => 4.,
+        }
+    }
+}
+
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub enum Transfer {
+    Linear,
+    Srgb,
+    Gamma(f64),
+}
+
+impl Transfer {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "linear" => Some(Transfer::Linear),
+            "srgb" => Some(Transfer::Srgb),
+            gamma => gamma
+                .parse()
+                .ok()
+                .filter(|gamma: &f64| *gamma > 0.)
+                .map(Transfer::Gamma),
+        }
+    }
+
+    fn encode(&self, value: f64) -> f64 {
+        match self {
+            Transfer::Linear => value,
+            Transfer::Srgb => {
+                if value <= 0.003_130_8 {
+                    value * 12.92
+                } else {
+                    1.055 * value.powf(1. / 2.4) - 0.055
+                }
+            }
+            Transfer::Gamma(gamma) => value.powf(1. / gamma),
+        }
+    }
+
+    pub fn decode(&self, value: f64) -> f64 {
+        match self {
+            Transfer::Linear => value,
+            Transfer::Srgb => {
+                if value <= 0.040_45 {
+                    value / 12.92
+                } else {
+                    ((value + 0.055) / 1.055).powf(2.4)
+                }
+            }
+            Transfer::Gamma(gamma) => value.powf(*gamma),
+        }
+    }
+}
+
+#[derive(Debug,Clone)]
+pub struct DisplayTransform {
+    pub exposure: f64,
+    pub operator: Operator,
+    pub white_point: Option<f64>,
+    pub transfer: Transfer,
+}
+
+impl DisplayTransform {
+    fn scale_luminance(color: Color, map: impl Fn(f64) -> f64) -> Color {
+        let luminance = color.luminance();
+        if luminance <= 0. {
+            return color;
+        }
+        color * (map(luminance) / luminance)
+    }
+
+    fn tonemap(&self, color: Color) -> Color {
+        let white = self
+            .white_point
+            .unwrap_or_else(|| self.operator.default_white_point());
+
+        match self.operator {
+            Operator::Clamp => color,
+            Operator::Reinhard => {
+                DisplayTransform::scale_luminance(color, |l| l / (1. + l))
+            }
+            Operator::ExtendedReinhard => {
+                DisplayTransform::scale_luminance(color, |l| {
+                    l * (1. + l / (white * white)) / (1. + l)
+                })
+            }
+            Operator::Aces => color.map(|x| {
+                (x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14)
+            }),
+            Operator::Hable => {
+                let curve = |x: f64| {
+                    let (a, b, c, d, e, f) = (0.15, 0.50, 0.10, 0.20, 0.02, 0.30);
+                    ((x * (a * x + c * b) + d * e) / (x * (a * x + b) + d * f)) - e / f
+                };
+                let scale = 1. / curve(2. * white);
+                color.map(|x| curve(2. * x) * scale)
+            }
+        }
+    }
+
+    pub fn apply(&self, color: Color) -> Color {
+        let exposed = color * 2f64.powf(self.exposure);
+        let mapped = self.tonemap(exposed.map(|x| x.max(0.)));
+        mapped.map(|x| self.transfer.encode(x.clamp(0., 1.)))
+    }
+}
+
+impl Default for DisplayTransform {
+    fn default() -> Self {
+        DisplayTransform {
+            exposure: 0.,
+            operator: Operator::Clamp,
+            white_point: None,
+            transfer: Transfer::Srgb,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OPERATORS: [Operator; 5] = [
+        Operator::Clamp,
+        Operator::Reinhard,
+        Operator::ExtendedReinhard,
+        Operator::Aces,
+        Operator::Hable,
+    ];
+
+    fn tonemap(operator: Operator, white_point: Option<f64>, value: f64) -> f64 {
+        let display = DisplayTransform { operator, white_point, ..DisplayTransform::default() };
+        display.tonemap(Color::gray(value)).r
+    }
+
+    #[test]
+    fn operators_map_black_to_black() {
+        for operator in OPERATORS {
+            assert!(tonemap(operator, None, 0.).abs() < 1e-12, "{:?}", operator);
+        }
+    }
+
+    #[test]
+    fn operators_are_monotonic() {
+        for operator in OPERATORS {
+            let mut previous = 0.;
+            for step in 1..=400 {
+                let mapped = tonemap(operator, None, step as f64 * 0.05);
+                assert!(mapped >= previous, "{:?} falls at {}", operator, step as f64 * 0.05);
+                previous = mapped;
+            }
+        }
+    }
+
+    #[test]
+    fn white_point_maps_to_white() {
+        for operator in [Operator::ExtendedReinhard, Operator::Hable] {
+            let default = operator.default_white_point();
+            assert!((tonemap(operator, None, default) - 1.).abs() < 1e-9, "{:?}", operator);
+
+            for white in [0.5, 2., 16.] {
+                let mapped = tonemap(operator, Some(white), white);
+                assert!((mapped - 1.).abs() < 1e-9, "{:?} maps {} to {}", operator, white, mapped);
+            }
+        }
+    }
+}
@@ -0,0 +1,59 @@
+use crate::color::Color;
+use crate::framebuffer::Framebuffer;
+use super::pixel::Pixel;
+
+// blue for the fewest samples through cyan, green and yellow to red for the most
+const HEAT: [Color; 5] = [
+    Color { r: 0., g: 0., b: 1. },
+    Color { r: 0., g: 1., b: 1. },
+    Color { r: 0., g: 1., b: 0. },
+    Color { r: 1., g: 1., b: 0. },
+    Color { r: 1., g: 0., b: 0. },
+];
+
+// keeps sampling a pixel after `min_samples` only while its relative error,
+// or a neighbor's, is above `threshold`
+#[derive(Debug,Clone)]
+pub struct Adaptive {
+    pub threshold: f64,
+    pub min_samples: u32,
+}
+
+impl Adaptive {
+    // looking at the 3x3 neighborhood keeps a pixel whose few samples
+    // happened to agree from stopping next to noisy ones
+    pub fn active(&self, pixels: &[Pixel], width: u32) -> Vec<bool> {
+        let width = width as usize;
+        let height = pixels.len() / width.max(1);
+        let errors: Vec<f64> = pixels.iter().map(Pixel::error).collect();
+
+        (0..pixels.len())
+            .map(|index| {
+                if pixels[index].samples < self.min_samples {
+                    return true;
+                }
+
+                let (x, y) = (index % width, index / width);
+                (y.saturating_sub(1)..(y + 2).min(height))
+                    .flat_map(|y| (x.saturating_sub(1)..(x + 2).min(width)).map(move |x| y * width + x))
+                    .any(|neighbor| errors[neighbor] > self.threshold)
+            })
+            .collect()
+    }
+}
+
+// false colors for the samples each pixel took, relative to the most any took
+pub fn heatmap(samples: &[u32], width: u32, height: u32) -> Framebuffer {
+    let most = samples.iter().copied().max().unwrap_or(0).max(1) as f64;
+    let mut framebuffer = Framebuffer::new(width, height);
+
+    for (index, count) in samples.iter().enumerate() {
+        let position = *count as f64 / most * (HEAT.len() - 1) as f64;
+        let stop = (position as usize).min(HEAT.len() - 2);
+        let t = position - stop as f64;
+        framebuffer.pixels[index] = HEAT[stop] * (1. - t) + HEAT[stop + 1] * t;
+        framebuffer.alpha[index] = 1.;
+    }
+
+    framebuffer
+}
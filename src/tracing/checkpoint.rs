@@ -0,0 +1,182 @@
+use std::convert::TryInto;
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+use crate::aov::Accumulator;
+use crate::aov::cryptomatte::Coverage;
+use crate::cli::Options;
+use crate::color::Color;
+use super::Job;
+use super::pixel::Pixel;
+
+const MAGIC: &[u8; 8] = b"TRACECKP";
+const VERSION: u32 = 2;
+
+// saves the state of every pixel to `path` every `interval`, and with
+// `resume` starts from what is saved there
+#[derive(Debug,Clone)]
+pub struct Checkpoint {
+    pub path: PathBuf,
+    pub interval: Duration,
+    pub resume: bool,
+}
+
+// everything that changes what a sample adds or which pixels a pass samples;
+// the sample count, budgets and output may differ between runs
+fn fingerprint(options: &Options, job: &Job) -> String {
+    let pass_samples = options.progressive.as_ref().map(|progressive| progressive.pass_samples);
+    format!(
+        "{:?}",
+        (
+            (&options.input, &options.stl, options.seed),
+            (options.width, options.height),
+            (&options.camera, &options.look_at, options.fov),
+            (&options.light, options.light_power),
+            (options.texture_filter, &options.environment),
+            (job.integrator, job.aovs, job.mattes),
+            (&options.adaptive, pass_samples),
+        )
+    )
+}
+
+fn put_u32(data: &mut Vec<u8>, value: u32) {
+    data.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_f64(data: &mut Vec<u8>, value: f64) {
+    data.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_color(data: &mut Vec<u8>, color: Color) {
+    for value in [color.r, color.g, color.b] {
+        put_f64(data, value);
+    }
+}
+
+struct Reader<'d> {
+    data: &'d [u8],
+}
+
+impl Reader<'_> {
+    fn bytes(&mut self, count: usize) -> Result<&[u8], String> {
+        if self.data.len() < count {
+            return Err("checkpoint is truncated".to_string());
+        }
+        let (bytes, rest) = self.data.split_at(count);
+        self.data = rest;
+        Ok(bytes)
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        Ok(u32::from_le_bytes(self.bytes(4)?.try_into().unwrap()))
+    }
+
+    fn f64(&mut self) -> Result<f64, String> {
+        Ok(f64::from_le_bytes(self.bytes(8)?.try_into().unwrap()))
+    }
+
+    fn color(&mut self) -> Result<Color, String> {
+        Ok(Color::new(self.f64()?, self.f64()?, self.f64()?))
+    }
+}
+
+fn put_pixel(data: &mut Vec<u8>, pixel: &Pixel) {
+    put_u32(data, pixel.samples);
+    put_color(data, pixel.total);
+    put_u32(data, pixel.hits);
+    put_f64(data, pixel.luminance);
+    put_f64(data, pixel.squares);
+    put_color(data, pixel.squared);
+
+    let accumulator = &pixel.accumulator;
+    for (total, center) in accumulator.totals.iter().zip(&accumulator.center) {
+        put_color(data, *total);
+        put_color(data, *center);
+    }
+    put_u32(data, accumulator.hits);
+    put_u32(data, accumulator.samples);
+
+    for coverage in &pixel.coverages {
+        put_u32(data, coverage.entries.len() as u32);
+        for (id, count) in &coverage.entries {
+            put_u32(data, *id);
+            put_u32(data, *count);
+        }
+    }
+}
+
+fn read_pixel(reader: &mut Reader, aovs: usize, mattes: usize) -> Result<Pixel, String> {
+    let samples = reader.u32()?;
+    let total = reader.color()?;
+    let hits = reader.u32()?;
+    let luminance = reader.f64()?;
+    let squares = reader.f64()?;
+    let squared = reader.color()?;
+
+    let mut totals = Vec::with_capacity(aovs);
+    let mut center = Vec::with_capacity(aovs);
+    for _ in 0..aovs {
+        totals.push(reader.color()?);
+        center.push(reader.color()?);
+    }
+    let accumulator = Accumulator {
+        totals,
+        center,
+        hits: reader.u32()?,
+        samples: reader.u32()?,
+    };
+
+    let mut coverages = Vec::with_capacity(mattes);
+    for _ in 0..mattes {
+        let count = reader.u32()?;
+        let entries = (0..count)
+            .map(|_| Ok((reader.u32()?, reader.u32()?)))
+            .collect::<Result<_, String>>()?;
+        coverages.push(Coverage { entries });
+    }
+
+    Ok(Pixel { samples, total, hits, luminance, squares, squared, accumulator, coverages })
+}
+
+// written next to the target and renamed over it, so a render killed while
+// saving still leaves the previous checkpoint intact
+pub fn save(checkpoint: &Checkpoint, options: &Options, job: &Job, pixels: &[Pixel]) -> Result<(), String> {
+    let fingerprint = fingerprint(options, job);
+    let mut data: Vec<u8> = MAGIC.to_vec();
+    put_u32(&mut data, VERSION);
+    put_u32(&mut data, fingerprint.len() as u32);
+    data.extend_from_slice(fingerprint.as_bytes());
+    put_u32(&mut data, pixels.len() as u32);
+    for pixel in pixels {
+        put_pixel(&mut data, pixel);
+    }
+
+    let error = |err: std::io::Error| format!("Cannot save checkpoint {}: {}", checkpoint.path.display(), err);
+    let partial = checkpoint.path.with_extension("partial");
+    fs::write(&partial, &data).map_err(error)?;
+    fs::rename(&partial, &checkpoint.path).map_err(error)
+}
+
+pub fn load(checkpoint: &Checkpoint, options: &Options, job: &Job) -> Result<Vec<Pixel>, String> {
+    let path = checkpoint.path.display();
+    let data = fs::read(&checkpoint.path).map_err(|err| format!("Cannot read checkpoint {}: {}", path, err))?;
+    let mut reader = Reader { data: &data };
+
+    if reader.bytes(MAGIC.len())? != MAGIC || reader.u32()? != VERSION {
+        return Err(format!("{} is not a checkpoint of this renderer", path));
+    }
+
+    let length = reader.u32()? as usize;
+    if reader.bytes(length)? != fingerprint(options, job).as_bytes() {
+        return Err(format!("{} was saved with a different scene, camera or render settings", path));
+    }
+
+    let count = reader.u32()? as usize;
+    if count != (options.width * options.height) as usize {
+        return Err(format!("{} does not match the image size", path));
+    }
+
+    (0..count)
+        .map(|_| read_pixel(&mut reader, job.aovs.len(), job.mattes.len()))
+        .collect()
+}
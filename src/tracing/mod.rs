@@ -1,45 +1,386 @@
-extern crate bmp;
+mod pixel;
+mod progressive;
+mod adaptive;
+mod checkpoint;
 
-use std::f64::INFINITY;
-use bmp::{Image,Pixel,consts as colors};
-use crate::geometry::{Point,Vector,Trigon,utils::trigon_brightness};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration,Instant};
+use crate::geometry::Vector;
+use crate::scene::Scene;
 use crate::tree::Octree;
-use crate::canvas;
+use crate::canvas::Canvas;
+use crate::cli::Options;
+use crate::color::Color;
+use crate::framebuffer::Framebuffer;
+use crate::sampling::{Rng,sample_seed};
+use crate::integrator::{Context,Integrator,Settings};
+use crate::environment::Environment;
+use crate::lights::Lights;
+use crate::aov::{Aov,Layer,Surface};
+use crate::aov::cryptomatte::{self,Matte};
+use pixel::Pixel;
 
+pub use progressive::Progressive;
+pub use adaptive::{Adaptive,heatmap};
+pub use checkpoint::Checkpoint;
+
+// samples per pass when adaptive sampling or checkpoints need passes but no
+// progressive pass size is given
+const PASS_SAMPLES: u32 = 4;
+
+fn sample_offset(index: u32) -> (f64, f64) {
+    // R2 low-discrepancy sequence, first sample lands on the pixel center
+    let a1 = 0.754_877_666_246_692_8;
+    let a2 = 0.569_840_290_998_053_3;
+    let x = (0.5 + a1 * index as f64).fract();
+    let y = (0.5 + a2 * index as f64).fract();
+    (x, y)
+}
+
+// the requested mattes, each with the ID of every object or material
+type Mattes = [(Matte, Vec<u32>)];
+
+// what one render asks for: the main image may want AOVs, mattes and
+// checkpoints, extra passes just their integrator
+pub struct Job<'j> {
+    pub integrator: &'j Settings,
+    pub aovs: &'j [Aov],
+    pub mattes: &'j [Matte],
+    pub checkpoint: Option<&'j Checkpoint>,
+}
+
+// the image with its AOV and Cryptomatte layers, the samples every pixel
+// took and the variance of its mean color
+pub struct Render {
+    pub framebuffer: Framebuffer,
+    pub layers: Vec<Layer>,
+    pub mattes: Vec<Layer>,
+    pub samples: Vec<u32>,
+    pub variance: Vec<Color>,
+}
+
+struct Tracer<'t> {
+    options: &'t Options,
+    canvas: &'t Canvas,
+    context: &'t Context<'t, 't>,
+    integrator: &'t dyn Integrator,
+    aovs: &'t [Aov],
+    mattes: &'t Mattes,
+}
+
+impl Tracer<'_> {
+    // adds `counts[x]` samples to every pixel of row `y`
+    fn trace_row(&self, y: u32, row: &mut [Pixel], counts: &[u32]) {
+        let options = self.options;
+        let forward: Vector = Vector::from(&options.look_at - &options.camera).normalize();
+
+        for (x, (pixel, count)) in row.iter_mut().zip(counts).enumerate() {
+            for _ in 0..*count {
+                let (dx, dy) = sample_offset(pixel.samples);
+                let point = self.canvas.point(x as f64 + dx, y as f64 + dy);
+                let direction = Vector::from(&point - &options.camera);
+                let mut rng = Rng::new(sample_seed(options.seed, x as u32, y, pixel.samples));
+                let sample = self.integrator.radiance(&options.camera, &direction, &mut rng);
+                pixel.add(sample);
+
+                if !self.aovs.is_empty() || !self.mattes.is_empty() {
+                    let surface = Surface::find(self.context, &options.camera, &direction, &forward);
+                    pixel.accumulator.add(self.aovs, surface.as_ref());
+
+                    if let Some(surface) = &surface {
+                        for ((matte, ids), coverage) in self.mattes.iter().zip(&mut pixel.coverages) {
+                            coverage.add(ids[matte.index(surface)]);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    // rows are handed out to the threads as they become free
+    fn trace_pass(&self, pixels: &mut [Pixel], counts: &[u32]) {
+        let width = self.options.width as usize;
+        let rows = Mutex::new(pixels.chunks_mut(width).zip(counts.chunks(width)).zip(0..));
+
+        thread::scope(|scope| {
+            for _ in 0..self.options.threads {
+                scope.spawn(|| loop {
+                    let next = rows.lock().expect("Render thread panicked").next();
+                    match next {
+                        Some(((row, counts), y)) => self.trace_row(y, row, counts),
+                        None => break,
+                    }
+                });
+            }
+        });
+    }
+
+    // samples every pixel takes in the next pass: up to `step`, none once
+    // it reaches the sample limit or, when adaptive, converges; a resumed
+    // pixel may already hold more than the limit
+    fn counts(&self, pixels: &[Pixel], step: u32) -> Vec<u32> {
+        let limit = self.options.samples;
+        let adaptive = self.options.adaptive.as_ref();
+        let active = match adaptive {
+            Some(adaptive) => adaptive.active(pixels, self.options.width),
+            None => vec![true; pixels.len()],
+        };
+
+        pixels
+            .iter()
+            .zip(active)
+            .map(|(pixel, active)| {
+                let min_samples = adaptive.map_or(0, |adaptive| adaptive.min_samples);
+                let left = limit.saturating_sub(pixel.samples);
+                match active {
+                    true if pixel.samples < min_samples => (min_samples - pixel.samples).min(left),
+                    true => step.min(left),
+                    false => 0,
+                }
+            })
+            .collect()
+    }
+
+    fn resolve(&self, pixels: &[Pixel]) -> Render {
+        let (width, height) = (self.options.width, self.options.height);
+        let mut framebuffer = Framebuffer::new(width, height);
+        let mut layers: Vec<Layer> = self
+            .aovs
+            .iter()
+            .map(|aov| Layer {
+                name: aov.name().to_string(),
+                channels: aov.channels(),
+                framebuffer: Framebuffer::new(width, height),
+            })
+            .collect();
+
+        for (index, pixel) in pixels.iter().enumerate() {
+            let (x, y) = (index as u32 % width, index as u32 / width);
+            let (color, alpha) = pixel.color();
+            framebuffer.set_pixel(x, y, color, alpha);
+            // AOVs are data, never blended with a background
+            for (layer, value) in layers.iter_mut().zip(pixel.accumulator.values(self.aovs)) {
+                layer.framebuffer.set_pixel(x, y, value, 1.);
+            }
+        }
+
+        let ranks = self.options.cryptomatte_ranks;
+        let mattes = self
+            .mattes
+            .iter()
+            .enumerate()
+            .flat_map(|(index, (matte, _))| {
+                let ranked: Vec<Vec<(u32, f64)>> = pixels
+                    .iter()
+                    .map(|pixel| pixel.coverages[index].ranked(ranks, pixel.samples))
+                    .collect();
+                cryptomatte::layers(*matte, &ranked, width, height, ranks)
+            })
+            .collect();
+
+        let samples = pixels.iter().map(|pixel| pixel.samples).collect();
+        let variance = pixels.iter().map(Pixel::color_variance).collect();
+        Render { framebuffer, layers, mattes, samples, variance }
+    }
+}
+
+// renders all samples at once, or in passes when progressive, adaptive or
+// checkpointed, handing the image so far to `report` every progressive interval
 pub fn trace(
-    width: u32,
-    height: u32,
-    camera_pos: &Point,
-    light_pos: &Point,
-    faces: &Vec<Trigon>
-) -> Image {
-    let tree = Octree::new(&faces);
-
-    let mut img = Image::new(width, height);
-    let pixels = canvas::build(height, width,-0.5,0.5,90.);
-
-    for (x, y) in img.coordinates() {
-        let pixel = &pixels[y as usize][x as usize];
-        let mut vector = Vector::from(pixel - camera_pos);
-        vector.set_origin(camera_pos);
-
-        let (distance, trigon) = tree.intersection(&vector);
-
-        if distance < INFINITY {
-            let brightness = trigon_brightness(
-                light_pos,
-                &trigon.unwrap(),
-                &tree,
-            );
-            img.set_pixel(
-                x,
-                y,
-                Pixel::new(brightness, brightness, brightness)
-            );
-        } else {
-            img.set_pixel(x, y, colors::WHITE);
-        }
-    }
-
-    img
+    options: &Options,
+    scene: &Scene,
+    environment: &dyn Environment,
+    job: &Job,
+    report: &mut dyn FnMut(&Render) -> Result<(), String>,
+) -> Result<Render, String> {
+    let aovs = job.aovs;
+    let tree = Octree::new(&scene.faces);
+    let lights = Lights::new(scene);
+    let canvas = Canvas::new(
+        options.width,
+        options.height,
+        &options.camera,
+        &options.look_at,
+        options.fov,
+    );
+    let context = Context {
+        scene,
+        tree: &tree,
+        light: &options.light,
+        light_power: options.light_power,
+        pixel_spread: canvas.pixel_spread(),
+        texture_filter: options.texture_filter,
+        environment,
+        environment_visible: options.environment.visible,
+        lights: &lights,
+    };
+    let integrator = job.integrator.build(&context);
+    let mattes: Vec<(Matte, Vec<u32>)> = job.mattes.iter().map(|matte| (*matte, matte.ids(scene))).collect();
+    let tracer = Tracer {
+        options,
+        canvas: &canvas,
+        context: &context,
+        integrator: integrator.as_ref(),
+        aovs,
+        mattes: &mattes,
+    };
+
+    let mut pixels: Vec<Pixel> = match job.checkpoint {
+        Some(checkpoint) if checkpoint.resume => checkpoint::load(checkpoint, options, job)?,
+        _ => (0..options.width * options.height).map(|_| Pixel::new(aovs, mattes.len())).collect(),
+    };
+
+    let passes = options.adaptive.is_some() || job.checkpoint.is_some();
+    let progressive = match &options.progressive {
+        Some(progressive) => progressive.clone(),
+        None if passes => Progressive {
+            pass_samples: PASS_SAMPLES,
+            interval: Duration::MAX,
+            ..Progressive::default()
+        },
+        None => {
+            let counts = vec![options.samples; pixels.len()];
+            tracer.trace_pass(&mut pixels, &counts);
+            return Ok(tracer.resolve(&pixels));
+        }
+    };
+
+    let started = Instant::now();
+    let mut reported = started;
+    let mut checkpointed = started;
+    let mut counts = tracer.counts(&pixels, progressive.pass_samples);
+
+    while counts.iter().any(|count| *count > 0) {
+        let pass = Instant::now();
+        tracer.trace_pass(&mut pixels, &counts);
+
+        let noise = progressive::noise(&pixels);
+        if progressive.finished(started.elapsed(), pass.elapsed(), noise) {
+            break;
+        }
+
+        counts = tracer.counts(&pixels, progressive.pass_samples);
+        if counts.iter().all(|count| *count == 0) {
+            break;
+        }
+
+        if reported.elapsed() >= progressive.interval {
+            report(&tracer.resolve(&pixels))?;
+            reported = Instant::now();
+        }
+
+        if let Some(checkpoint) = job.checkpoint {
+            if checkpointed.elapsed() >= checkpoint.interval {
+                checkpoint::save(checkpoint, options, job, &pixels)?;
+                checkpointed = Instant::now();
+            }
+        }
+    }
+
+    // the final state too, so a later run can add samples to it
+    if let Some(checkpoint) = job.checkpoint {
+        checkpoint::save(checkpoint, options, job, &pixels)?;
+    }
+
+    Ok(tracer.resolve(&pixels))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use super::*;
+    use crate::cli::{self,Command};
+    use crate::geometry::{Point,Trigon};
+    use crate::material::Material;
+
+    // a floor and a back wall in front of the default camera
+    fn scene<'a>() -> Scene<'a> {
+        let corner = |x: f64, y: f64, z: f64| Point::new(x, y, z);
+        let faces = vec![
+            Trigon::new(corner(-1., -1., -0.5), corner(1., -1., -0.5), corner(1., 1., -0.5)),
+            Trigon::new(corner(-1., -1., -0.5), corner(1., 1., -0.5), corner(-1., 1., -0.5)),
+            Trigon::new(corner(-1., 1., -0.5), corner(1., 1., -0.5), corner(1., 1., 1.)),
+            Trigon::new(corner(-1., 1., -0.5), corner(1., 1., 1.), corner(-1., 1., 1.)),
+        ];
+        Scene::new(faces, vec![Material::default()], vec!["default".to_string()])
+    }
+
+    fn options(extra: &[&str]) -> Options {
+        let args: Vec<String> = ["-i", "test.obj", "-r", "8x6", "--integrator", "path"]
+            .iter()
+            .chain(extra)
+            .map(|arg| arg.to_string())
+            .collect();
+        match cli::parse(&args) {
+            Ok(Command::Render(options)) => *options,
+            _ => panic!("invalid test options {:?}", args),
+        }
+    }
+
+    fn render(options: &Options, aovs: &[Aov]) -> Result<Render, String> {
+        let scene = scene();
+        let environment = options.environment.build()?;
+        let job = Job {
+            integrator: &options.integrator,
+            aovs,
+            mattes: &[],
+            checkpoint: options.checkpoint.as_ref(),
+        };
+        trace(options, &scene, environment.as_ref(), &job, &mut |_| Ok(()))
+    }
+
+    fn assert_identical(a: &Render, b: &Render) {
+        assert_eq!(a.framebuffer.pixels, b.framebuffer.pixels);
+        assert_eq!(a.framebuffer.alpha, b.framebuffer.alpha);
+        assert_eq!(a.samples, b.samples);
+        for (a, b) in a.layers.iter().zip(&b.layers) {
+            assert_eq!(a.framebuffer.pixels, b.framebuffer.pixels);
+        }
+    }
+
+    #[test]
+    fn thread_count_does_not_change_the_image() {
+        let aovs = [Aov::Normal];
+        let one = render(&options(&["-s", "8", "-t", "1"]), &aovs).unwrap();
+        let four = render(&options(&["-s", "8", "-t", "4"]), &aovs).unwrap();
+        assert_identical(&one, &four);
+    }
+
+    #[test]
+    fn passes_do_not_change_the_image() {
+        let single = render(&options(&["-s", "12", "--seed", "3"]), &[]).unwrap();
+        let passes = render(&options(&["-s", "12", "--seed", "3", "--progressive", "--pass-samples", "5"]), &[]).unwrap();
+        let reseeded = render(&options(&["-s", "12", "--seed", "4"]), &[]).unwrap();
+        assert_identical(&single, &passes);
+        assert_ne!(single.framebuffer.pixels, reseeded.framebuffer.pixels);
+    }
+
+    #[test]
+    fn resumed_render_matches_uninterrupted() {
+        let path = std::env::temp_dir().join(format!("trace-resume-{}.ckpt", std::process::id()));
+        let checkpoint = path.to_str().unwrap();
+        let aovs = [Aov::Albedo, Aov::Depth];
+
+        let uninterrupted = render(&options(&["-s", "16"]), &aovs).unwrap();
+        render(&options(&["-s", "8", "--checkpoint", checkpoint]), &aovs).unwrap();
+        let resumed = render(&options(&["-s", "16", "--checkpoint", checkpoint, "--resume"]), &aovs);
+        let changed = render(&options(&["-s", "16", "--checkpoint", checkpoint, "--resume", "--adaptive", "0.1"]), &aovs);
+        fs::remove_file(&path).ok();
+
+        assert_identical(&uninterrupted, &resumed.unwrap());
+        assert!(changed.is_err());
+    }
+
+    #[test]
+    fn resuming_with_fewer_samples_keeps_the_checkpoint() {
+        let path = std::env::temp_dir().join(format!("trace-fewer-{}.ckpt", std::process::id()));
+        let checkpoint = path.to_str().unwrap();
+
+        let full = render(&options(&["-s", "16", "--checkpoint", checkpoint]), &[]).unwrap();
+        let resumed = render(&options(&["-s", "8", "--checkpoint", checkpoint, "--resume"]), &[]);
+        fs::remove_file(&path).ok();
+
+        assert_identical(&full, &resumed.unwrap());
+    }
 }
@@ -0,0 +1,89 @@
+use crate::color::{Color,BLACK};
+use crate::aov::{Accumulator,Aov};
+use crate::aov::cryptomatte::Coverage;
+
+// luminance below this counts as this dark when judging noise, so near-black
+// pixels don't need endless samples
+const NOISE_FLOOR: f64 = 0.1;
+
+// everything one pixel has gathered so far, kept between passes
+pub struct Pixel {
+    pub samples: u32,
+    pub total: Color,
+    pub hits: u32,
+    // sums of the sample luminance and its square
+    pub luminance: f64,
+    pub squares: f64,
+    // per-channel sums of squares, for the denoiser
+    pub squared: Color,
+    pub accumulator: Accumulator,
+    pub coverages: Vec<Coverage>,
+}
+
+impl Pixel {
+    pub fn new(aovs: &[Aov], mattes: usize) -> Self {
+        Pixel {
+            samples: 0,
+            total: BLACK,
+            hits: 0,
+            luminance: 0.,
+            squares: 0.,
+            squared: BLACK,
+            accumulator: Accumulator::new(aovs),
+            coverages: (0..mattes).map(|_| Coverage::default()).collect(),
+        }
+    }
+
+    // a miss adds nothing but still counts against coverage
+    pub fn add(&mut self, sample: Option<Color>) {
+        self.samples += 1;
+
+        if let Some(color) = sample {
+            let luminance = color.luminance();
+            self.total += color;
+            self.hits += 1;
+            self.luminance += luminance;
+            self.squares += luminance * luminance;
+            self.squared += color * color;
+        }
+    }
+
+    // premultiplied color and coverage
+    pub fn color(&self) -> (Color, f64) {
+        let samples = self.samples.max(1) as f64;
+        (self.total * (1. / samples), self.hits as f64 / samples)
+    }
+
+    // variance of the mean luminance, unknown before two samples
+    pub fn variance(&self) -> f64 {
+        if self.samples < 2 {
+            return f64::INFINITY;
+        }
+
+        let samples = self.samples as f64;
+        let mean = self.luminance / samples;
+        ((self.squares - samples * mean * mean) / (samples - 1.)).max(0.) / samples
+    }
+
+    // variance of the mean of every channel, unknown before two samples
+    pub fn color_variance(&self) -> Color {
+        if self.samples < 2 {
+            return Color::gray(f64::INFINITY);
+        }
+
+        let samples = self.samples as f64;
+        let mean = self.total * (1. / samples);
+        let variance = |squared: f64, mean: f64| ((squared - samples * mean * mean) / (samples - 1.)).max(0.) / samples;
+        Color::new(
+            variance(self.squared.r, mean.r),
+            variance(self.squared.g, mean.g),
+            variance(self.squared.b, mean.b),
+        )
+    }
+
+    // standard error of the mean luminance relative to the luminance itself
+    pub fn error(&self) -> f64 {
+        let mean = self.luminance / self.samples.max(1) as f64;
+        self.variance().sqrt() / mean.max(NOISE_FLOOR)
+    }
+}
@@ -0,0 +1,39 @@
+use std::time::Duration;
+use super::pixel::Pixel;
+
+// renders in passes of `pass_samples` until the sample count, `time_limit`
+// or `noise_threshold` is reached, reporting the image every `interval`
+#[derive(Debug,Clone)]
+pub struct Progressive {
+    pub pass_samples: u32,
+    pub time_limit: Option<Duration>,
+    pub noise_threshold: Option<f64>,
+    pub interval: Duration,
+}
+
+impl Default for Progressive {
+    fn default() -> Self {
+        Progressive {
+            pass_samples: 1,
+            time_limit: None,
+            noise_threshold: None,
+            interval: Duration::from_secs(10),
+        }
+    }
+}
+
+impl Progressive {
+    // stop early rather than let the next pass, assumed as slow as the last,
+    // overrun the time limit
+    pub fn finished(&self, elapsed: Duration, pass: Duration, noise: f64) -> bool {
+        let out_of_time = self.time_limit.is_some_and(|limit| elapsed + pass > limit);
+        let converged = self.noise_threshold.is_some_and(|threshold| noise <= threshold);
+        out_of_time || converged
+    }
+}
+
+// root mean square of the per-pixel relative error
+pub fn noise(pixels: &[Pixel]) -> f64 {
+    let total: f64 = pixels.iter().map(|pixel| pixel.error().powi(2)).sum();
+    (total / pixels.len().max(1) as f64).sqrt()
+}
@@ -1,4 +1,3 @@
-use std::f64::INFINITY;
 use crate::geometry::{
     Point,
     Vector,
@@ -122,6 +121,6 @@ impl BoundingBox {
     }
 
     pub fn intersects(&self, vector: &Vector) -> bool {
-        vector_box_intersection(vector, &self.min, &self.max) < INFINITY
+        vector_box_intersection(vector, &self.min, &self.max) < f64::INFINITY
     }
 }
@@ -1,8 +1,8 @@
-use std::f64::INFINITY;
 use super::BoundingBox;
 use crate::geometry::{Vector,Trigon,intersection};
 
 const COUNT_OF_TRIGONS_IN_NODE: usize = 20;
+const MAX_DEPTH: u32 = 16;
 
 #[derive(Debug)]
 pub struct Octree<'a> {
@@ -13,19 +13,17 @@ pub struct Octree<'a> {
 
 impl<'a> Octree<'a> {
     pub fn new(faces: &'a Vec<Trigon<'a>>) -> Self {
-        let bounded_faces: Vec<&Trigon> = faces
-            .iter()
-            .map(|face| face)
-            .collect();
-        let bounding_box = BoundingBox::from(&faces);
-        return Octree::leaf_or_node(&bounding_box, bounded_faces);
+        let bounded_faces: Vec<&Trigon> = faces.iter().collect();
+        let bounding_box = BoundingBox::from(faces);
+        Octree::leaf_or_node(&bounding_box, bounded_faces, 0)
     }
 
     fn leaf_or_node(
         bounding_box: &BoundingBox,
-        bounded_faces: Vec<&'a Trigon<'a>>
+        bounded_faces: Vec<&'a Trigon<'a>>,
+        depth: u32,
     ) -> Self {
-        if bounded_faces.len() <= COUNT_OF_TRIGONS_IN_NODE {
+        if bounded_faces.len() <= COUNT_OF_TRIGONS_IN_NODE || depth >= MAX_DEPTH {
             return Octree {
                 bounding_box: bounding_box.clone(),
                 children: vec![],
@@ -38,8 +36,8 @@ impl<'a> Octree<'a> {
 
         for subbox in bounding_subboxes.iter() {
             let subbounded_faces = subbox.get_bounded(&bounded_faces);
-            if subbounded_faces.len() == 0 { continue; }
-            let child = Octree::leaf_or_node(subbox, subbounded_faces);
+            if subbounded_faces.is_empty() { continue; }
+            let child = Octree::leaf_or_node(subbox, subbounded_faces, depth + 1);
             children.push(child);
         }
 
@@ -54,7 +52,7 @@ impl<'a> Octree<'a> {
         &self,
         vector: &Vector
     ) -> (f64, Option<&'a Trigon<'a>>) {
-        let mut min_distance = INFINITY;
+        let mut min_distance = f64::INFINITY;
         let mut trigon = None;
 
         for &face in &self.faces {
@@ -73,12 +71,12 @@ impl<'a> Octree<'a> {
         vector: &Vector
     ) -> (f64, Option<&'a Trigon<'a>>) {
         if !self.bounding_box.intersects(vector) {
-            return (INFINITY, None);
+            return (f64::INFINITY, None);
         }
 
-        if self.faces.len() > 0 {
+        if !self.faces.is_empty() {
             return self.face_intersection(vector);
-        } else if self.children.len() > 0 {
+        } else if !self.children.is_empty() {
             let mut child_intersections: Vec<(f64, Option<&'a Trigon<'a>>)> =
                 self.children
                     .iter()
@@ -90,6 +88,17 @@ impl<'a> Octree<'a> {
             return child_intersections.remove(0);
         }
 
-        (INFINITY, None)
+        (f64::INFINITY, None)
+    }
+
+    // any hit closer than `distance` along `vector`, stopping at the first
+    // one found instead of looking for the nearest
+    pub fn occluded(&self, vector: &Vector, distance: f64) -> bool {
+        if !self.bounding_box.intersects(vector) {
+            return false;
+        }
+
+        self.faces.iter().any(|face| intersection(vector, face) < distance)
+            || self.children.iter().any(|child| child.occluded(vector, distance))
     }
 }
\ No newline at end of file